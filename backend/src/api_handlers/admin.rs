@@ -1,18 +1,34 @@
 use axum::{
     extract::{Json, State},
-    http::StatusCode,
-    response::Json as JsonResponse,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json as JsonResponse, Response},
     routing::{post, get},
     Router,
 };
+use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use crate::audit::{self, AuditEvent};
+use crate::bayes;
+use crate::cookies;
+use crate::auth::{self, Actor, AdminIdentity, ClientIp, Principal, Role, Scope, TokenKind};
 use crate::db::AppState;
+use crate::email_templates;
+use crate::lockout;
+use crate::mail;
+use crate::outbox;
+use crate::password_policy;
+use crate::password_reset;
+use crate::sessions::{self, RefreshError, SessionTokens};
+use super::two_factor;
 
 #[derive(Deserialize)]
 pub struct LoginRequest {
     username: String,
     password: String,
+    // Keep the session in HttpOnly cookies instead of handing tokens to the page
+    #[serde(default)]
+    cookie: bool,
 }
 
 #[derive(Serialize)]
@@ -20,103 +36,497 @@ pub struct LoginResponse {
     success: bool,
     message: String,
     token: Option<String>,
+    refresh_token: Option<String>,
+    must_change_password: bool,
+    two_factor_required: bool,
+    challenge_token: Option<String>,
+    csrf_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    // Absent for cookie-mode sessions, which send it as a cookie
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RefreshResponse {
+    token: String,
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorLoginRequest {
+    challenge_token: String,
+    code: String,
+    #[serde(default)]
+    cookie: bool,
 }
 
 #[derive(Serialize)]
-pub struct ContactSubmission {
+pub struct MeResponse {
     id: i64,
-    name: String,
-    email: String,
-    subject: String,
-    message: String,
-    created_at: String,
-    read: bool,
+    username: String,
+    role: Role,
+    must_change_password: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    current_password: String,
+    new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    username: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    token: String,
+    new_password: String,
 }
 
 #[derive(Serialize)]
-pub struct ContactsResponse {
-    contacts: Vec<ContactSubmission>,
+pub struct RetrainResponse {
+    spam_documents: i64,
+    ham_documents: i64,
+    reclassified: usize,
 }
 
 pub fn router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/login", post(login))
-        .route("/contacts", get(list_contacts))
-        .route("/contacts/:id/read", post(mark_contact_read))
+        .route("/login/2fa", post(login_two_factor))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .route("/me", get(me))
+        .route("/password", post(change_password))
+        .route("/password-reset", post(request_password_reset))
+        .route("/password-reset/confirm", post(confirm_password_reset))
+        .route("/spam/retrain", post(retrain_spam_filter))
         .with_state(state)
 }
 
 async fn login(
     State(state): State<Arc<AppState>>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
     Json(request): Json<LoginRequest>,
-) -> Result<JsonResponse<LoginResponse>, StatusCode> {
+) -> Result<Response, StatusCode> {
+    let attempt_keys = [lockout::username_key(&request.username), lockout::ip_key(&client_ip)];
+
+    let account = {
+        let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if let Some(wait) = lockout::retry_after(&conn, &attempt_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+            tracing::warn!("Login for {} from {} throttled for {}s", request.username, client_ip, wait);
+            return Ok((StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, wait.to_string())]).into_response());
+        }
+
+        // Counted as a failure until the password checks out, so requests made in parallel
+        // while bcrypt runs can't get around the lockout
+        lockout::record_failure(&conn, &attempt_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        conn.query_row(
+            "SELECT id, password_hash, must_change_password, totp_enabled FROM admin_users WHERE username = ?1 AND disabled = 0",
+            [&request.username],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)? != 0, row.get::<_, i64>(3)? != 0)),
+        ).optional().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+
+    let (valid, new_hash) = check_password(request.password, account.as_ref().map(|(_, hash, _, _)| hash.clone())).await?;
+
     let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    // Get stored password hash
-    let stored_hash: String = conn.query_row(
-        "SELECT password_hash FROM admin_users WHERE username = ?1",
-        [&request.username],
-        |row| row.get(0),
+    // An unknown username gets exactly the answer a wrong password does, so neither the
+    // response nor lockouts reveal which accounts exist
+    let Some((user_id, _, must_change_password, totp_enabled)) = account.filter(|_| valid) else {
+        audit::record_failed_login(&conn, &request.username, &client_ip)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        return Ok(JsonResponse(LoginResponse {
+            success: false,
+            message: "Invalid credentials".to_string(),
+            token: None,
+            refresh_token: None,
+            must_change_password: false,
+            two_factor_required: false,
+            challenge_token: None,
+            csrf_token: None,
+        }).into_response());
+    };
+
+    // Upgrade hashes made before BCRYPT_COST was raised while we have the plaintext
+    if let Some(password_hash) = new_hash {
+        conn.execute(
+            "UPDATE admin_users SET password_hash = ?1 WHERE id = ?2",
+            rusqlite::params![password_hash, user_id],
+        ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    if totp_enabled {
+        // No session until the second factor checks out at /login/2fa, which also clears
+        // the failure recorded above
+        return Ok(JsonResponse(LoginResponse {
+            success: false,
+            message: "Two-factor code required".to_string(),
+            token: None,
+            refresh_token: None,
+            must_change_password,
+            two_factor_required: true,
+            challenge_token: Some(auth::issue_two_factor_challenge(&state.token_secret, user_id)),
+            csrf_token: None,
+        }).into_response());
+    }
+
+    lockout::reset(&conn, &attempt_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let tokens = sessions::start(&conn, &state.token_secret, user_id, user_agent(&headers), &client_ip)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    record_login(&conn, user_id, &request.username, &client_ip)?;
+
+    Ok(session_response(&state.token_secret, tokens, must_change_password, request.cookie))
+}
+
+/// Checks a login password on the blocking pool, so bcrypt neither holds the database lock
+/// nor stalls the runtime. Without an account it checks against a dummy hash, taking as long
+/// as a wrong password would. Also returns a fresh hash when the stored one predates the
+/// current BCRYPT_COST.
+async fn check_password(password: String, stored_hash: Option<String>) -> Result<(bool, Option<String>), StatusCode> {
+    tokio::task::spawn_blocking(move || {
+        let Some(stored_hash) = stored_hash else {
+            let _ = bcrypt::verify(&password, password_policy::dummy_hash());
+            return Ok((false, None));
+        };
+
+        let valid = bcrypt::verify(&password, &stored_hash).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let new_hash = if valid && password_policy::needs_rehash(&stored_hash) {
+            Some(password_policy::hash(&password).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?)
+        } else {
+            None
+        };
+
+        Ok((valid, new_hash))
+    }).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+async fn login_two_factor(
+    State(state): State<Arc<AppState>>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    Json(request): Json<TwoFactorLoginRequest>,
+) -> Result<Response, StatusCode> {
+    let claims = auth::verify_token(&state.token_secret, &request.challenge_token, TokenKind::TwoFactor)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (username, must_change_password): (String, bool) = conn.query_row(
+        "SELECT username, must_change_password FROM admin_users WHERE id = ?1 AND disabled = 0",
+        [claims.sub],
+        |row| Ok((row.get(0)?, row.get::<_, i64>(1)? != 0)),
     ).map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    // Verify password
-    let valid = bcrypt::verify(&request.password, &stored_hash)
+    let attempt_keys = [lockout::username_key(&username), lockout::ip_key(&client_ip)];
+
+    if let Some(wait) = lockout::retry_after(&conn, &attempt_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Ok((StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, wait.to_string())]).into_response());
+    }
+
+    let valid = two_factor::verify_code(&conn, claims.sub, &request.code)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !valid {
+        lockout::record_failure(&conn, &attempt_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        audit::record_failed_login(&conn, &username, &client_ip)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    lockout::reset(&conn, &attempt_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let tokens = sessions::start(&conn, &state.token_secret, claims.sub, user_agent(&headers), &client_ip)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    record_login(&conn, claims.sub, &username, &client_ip)?;
+
+    Ok(session_response(&state.token_secret, tokens, must_change_password, request.cookie))
+}
 
-    if valid {
-        // In production, generate a proper JWT token
-        // For now, using a simple token
-        Ok(JsonResponse(LoginResponse {
-            success: true,
-            message: "Login successful".to_string(),
-            token: Some("admin-token-12345".to_string()),
-        }))
+/// Answers a successful login with the session's tokens, or in cookie mode with
+/// session cookies and only the CSRF token in the body.
+fn session_response(token_secret: &[u8], tokens: SessionTokens, must_change_password: bool, cookie: bool) -> Response {
+    let body = |token, refresh_token, csrf_token| LoginResponse {
+        success: true,
+        message: "Login successful".to_string(),
+        token,
+        refresh_token,
+        must_change_password,
+        two_factor_required: false,
+        challenge_token: None,
+        csrf_token,
+    };
+
+    if cookie {
+        let csrf_token = auth::csrf_token(token_secret, &tokens.session_id);
+        (cookies::set_session(token_secret, &tokens), JsonResponse(body(None, None, Some(csrf_token)))).into_response()
     } else {
-        Ok(JsonResponse(LoginResponse {
-            success: false,
-            message: "Invalid credentials".to_string(),
-            token: None,
-        }))
+        JsonResponse(body(Some(tokens.access_token), Some(tokens.refresh_token), None)).into_response()
     }
 }
 
-async fn list_contacts(
+fn record_login(conn: &rusqlite::Connection, user_id: i64, username: &str, client_ip: &str) -> Result<(), StatusCode> {
+    audit::record(conn, AuditEvent {
+        actor: &Actor::Admin { id: user_id, username: username.to_string() },
+        action: "login.success",
+        target_id: Some(user_id),
+        client_ip,
+        before: None,
+        after: None,
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn user_agent(headers: &HeaderMap) -> &str {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown")
+}
+
+async fn refresh(
     State(state): State<Arc<AppState>>,
-) -> Result<JsonResponse<ContactsResponse>, StatusCode> {
+    headers: HeaderMap,
+    Json(request): Json<RefreshRequest>,
+) -> Result<Response, StatusCode> {
+    let (refresh_token, cookie) = match request.refresh_token {
+        Some(token) => (token, false),
+        None => {
+            let token = cookies::get(&headers, cookies::REFRESH_COOKIE).ok_or(StatusCode::UNAUTHORIZED)?;
+
+            // Refresh tokens are `<session id>.<secret>`, and the CSRF token is bound to the session
+            let session_id = token.split_once('.').map(|(id, _)| id).ok_or(StatusCode::UNAUTHORIZED)?;
+            let presented = headers.get(cookies::CSRF_HEADER).and_then(|v| v.to_str().ok());
+            if !auth::verify_csrf(&state.token_secret, session_id, presented) {
+                return Err(StatusCode::FORBIDDEN);
+            }
+
+            (token.to_string(), true)
+        }
+    };
+
     let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let mut stmt = conn.prepare(
-        "SELECT id, name, email, subject, message, created_at, read 
-         FROM contacts ORDER BY created_at DESC"
+    let tokens = sessions::refresh(&conn, &state.token_secret, &refresh_token)
+        .map_err(|e| match e {
+            RefreshError::Invalid => StatusCode::UNAUTHORIZED,
+            RefreshError::Database(e) => {
+                tracing::error!("Failed to refresh admin session: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    if cookie {
+        return Ok((StatusCode::NO_CONTENT, cookies::set_session(&state.token_secret, &tokens)).into_response());
+    }
+
+    Ok(JsonResponse(RefreshResponse {
+        token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+    }).into_response())
+}
+
+async fn logout(
+    State(state): State<Arc<AppState>>,
+    admin: AdminIdentity,
+) -> Result<(StatusCode, cookies::SetCookies), StatusCode> {
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    sessions::revoke(&conn, &admin.session_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok((StatusCode::NO_CONTENT, cookies::clear_session()))
+}
+
+async fn me(admin: AdminIdentity) -> JsonResponse<MeResponse> {
+    JsonResponse(MeResponse {
+        id: admin.id,
+        username: admin.username,
+        role: admin.role,
+        must_change_password: admin.must_change_password,
+    })
+}
+
+async fn change_password(
+    State(state): State<Arc<AppState>>,
+    admin: AdminIdentity,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<StatusCode, StatusCode> {
+    if request.new_password == request.current_password {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    password_policy::validate(&request.new_password)?;
+
+    let stored_hash: String = {
+        let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.query_row(
+            "SELECT password_hash FROM admin_users WHERE id = ?1",
+            [admin.id],
+            |row| row.get(0),
+        ).map_err(|_| StatusCode::UNAUTHORIZED)?
+    };
+
+    // bcrypt runs on the blocking pool so it neither holds the database lock nor stalls the runtime
+    let password_hash = tokio::task::spawn_blocking(move || {
+        let valid = bcrypt::verify(&request.current_password, &stored_hash)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if !valid {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        password_policy::hash(&request.new_password).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    }).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    conn.execute(
+        "UPDATE admin_users SET password_hash = ?1, must_change_password = 0 WHERE id = ?2",
+        rusqlite::params![password_hash, admin.id],
     ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let contacts = stmt.query_map([], |row| {
-        Ok(ContactSubmission {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            email: row.get(2)?,
-            subject: row.get(3)?,
-            message: row.get(4)?,
-            created_at: row.get(5)?,
-            read: row.get::<_, i64>(6)? != 0,
-        })
-    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Sign out everywhere else in case the old password leaked
+    sessions::revoke_all_for_user(&conn, admin.id, Some(&admin.session_id))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Emails a reset link if the username belongs to an enabled admin with an email address.
+/// Answers 202 either way so the response doesn't reveal which usernames exist; 429 once
+/// the account or client has asked too often, whether or not the account exists.
+async fn request_password_reset(
+    State(state): State<Arc<AppState>>,
+    ClientIp(client_ip): ClientIp,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<Response, StatusCode> {
+    let attempt_keys = lockout::password_reset_keys(&request.username, &client_ip);
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if let Some(wait) = lockout::retry_after(&conn, &attempt_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        tracing::warn!("Password reset for {} from {} throttled for {}s", request.username, client_ip, wait);
+        return Ok((StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, wait.to_string())]).into_response());
+    }
+
+    // Every request counts, so the login backoff applies to flooding someone's inbox too
+    lockout::record_failure(&conn, &attempt_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let account: Option<(i64, String)> = conn.query_row(
+        "SELECT id, email FROM admin_users WHERE username = ?1 AND disabled = 0 AND email IS NOT NULL",
+        [&request.username],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let contact_list: Vec<ContactSubmission> = contacts.filter_map(|c| c.ok()).collect();
+    let Some((user_id, email)) = account else {
+        return Ok(StatusCode::ACCEPTED.into_response());
+    };
 
-    Ok(JsonResponse(ContactsResponse { contacts: contact_list }))
+    let tx = conn.unchecked_transaction().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let token = password_reset::create(&tx, user_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let rendered = email_templates::render(&tx, "password_reset", &serde_json::json!({
+        "username": request.username,
+        "link": format!("{}/admin/reset-password?token={}", site_url(), token),
+        "expires_minutes": password_reset::reset_ttl_secs() / 60,
+    })).map_err(|e| {
+        tracing::error!("Failed to render password reset email: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // The link waits in the outbox until sent. It's single-use and short-lived, and the
+    // admin outbox listing never shows message bodies.
+    let reset_email = mail::Email {
+        from_name: "Portfolio Admin".to_string(),
+        to: email,
+        subject: rendered.subject,
+        text: rendered.text,
+        html: Some(rendered.html),
+        headers: Vec::new(),
+    };
+    outbox::enqueue(&tx, "password_reset", &reset_email).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::ACCEPTED.into_response())
+}
+
+/// Base URL for links in emails; SITE_URL, falling back to the address the server listens on.
+fn site_url() -> String {
+    std::env::var("SITE_URL").unwrap_or_else(|_| {
+        format!("http://{}", std::env::var("PUBLIC_HOST").unwrap_or_else(|_| "localhost:3000".to_string()))
+    })
 }
 
-async fn mark_contact_read(
+async fn confirm_password_reset(
     State(state): State<Arc<AppState>>,
-    axum::extract::Path(id): axum::extract::Path<i64>,
+    Json(request): Json<PasswordResetConfirmRequest>,
 ) -> Result<StatusCode, StatusCode> {
+    password_policy::validate(&request.new_password)?;
+
+    // Hashed on the blocking pool, before taking the lock
+    let new_password = request.new_password;
+    let password_hash = tokio::task::spawn_blocking(move || password_policy::hash(&new_password))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let user_id = password_reset::consume(&conn, &request.token)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let username: String = conn.query_row(
+        "SELECT username FROM admin_users WHERE id = ?1",
+        [user_id],
+        |row| row.get(0),
+    ).map_err(|_| StatusCode::BAD_REQUEST)?;
+
     conn.execute(
-        "UPDATE contacts SET read = 1 WHERE id = ?1",
-        [id],
+        "UPDATE admin_users SET password_hash = ?1, must_change_password = 0 WHERE id = ?2",
+        rusqlite::params![password_hash, user_id],
     ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    // Whoever knew the old password loses every session, and the owner isn't stuck behind a lockout
+    sessions::revoke_all_for_user(&conn, user_id, None)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    lockout::reset(&conn, &[lockout::username_key(&username)])
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     Ok(StatusCode::OK)
 }
+
+/// Rebuilds the spam filter from every marked contact and rescores all contacts.
+async fn retrain_spam_filter(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+) -> Result<JsonResponse<RetrainResponse>, StatusCode> {
+    caller.require(Scope::ContactsWrite)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let tx = conn.unchecked_transaction().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let summary = bayes::retrain(&tx).map_err(|e| {
+        tracing::error!("Failed to retrain spam filter: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tracing::info!("Spam filter retrained by {}", caller.actor);
+
+    Ok(JsonResponse(RetrainResponse {
+        spam_documents: summary.spam_documents,
+        ham_documents: summary.ham_documents,
+        reclassified: summary.reclassified,
+    }))
+}
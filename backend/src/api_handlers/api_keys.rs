@@ -0,0 +1,124 @@
+use axum::{
+    extract::{Json, State, Path},
+    http::StatusCode,
+    response::Json as JsonResponse,
+    routing::{delete, get},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::api_keys;
+use crate::auth::{AdminUser, Role, Scope};
+use crate::db::AppState;
+
+#[derive(Serialize)]
+pub struct ApiKeySummary {
+    id: i64,
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: String,
+    last_used_at: Option<String>,
+    revoked: bool,
+}
+
+#[derive(Serialize)]
+pub struct ApiKeysResponse {
+    api_keys: Vec<ApiKeySummary>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyRequest {
+    name: String,
+    scopes: Vec<Scope>,
+}
+
+#[derive(Serialize)]
+pub struct CreateApiKeyResponse {
+    id: i64,
+    name: String,
+    scopes: Vec<Scope>,
+    // Shown once; only a hash is kept
+    key: String,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(list_api_keys).post(create_api_key))
+        .route("/:id", delete(revoke_api_key))
+        .with_state(state)
+}
+
+async fn list_api_keys(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+) -> Result<JsonResponse<ApiKeysResponse>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, name, scopes, created_at, last_used_at, revoked_at IS NOT NULL
+         FROM api_keys ORDER BY created_at DESC"
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let keys = stmt.query_map([], |row| {
+        Ok(ApiKeySummary {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            scopes: api_keys::scopes_from_string(&row.get::<_, String>(2)?),
+            created_at: row.get(3)?,
+            last_used_at: row.get(4)?,
+            revoked: row.get(5)?,
+        })
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let key_list: Vec<ApiKeySummary> = keys.filter_map(|k| k.ok()).collect();
+
+    Ok(JsonResponse(ApiKeysResponse { api_keys: key_list }))
+}
+
+async fn create_api_key(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<JsonResponse<CreateApiKeyResponse>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let name = request.name.trim();
+    if name.is_empty() || request.scopes.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let new_key = api_keys::create(&conn, name, &request.scopes, admin.id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(CreateApiKeyResponse {
+        id: new_key.id,
+        name: name.to_string(),
+        scopes: request.scopes,
+        key: new_key.key,
+    }))
+}
+
+async fn revoke_api_key(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let updated = conn.execute(
+        "UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP WHERE id = ?1 AND revoked_at IS NULL",
+        [id],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if updated == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
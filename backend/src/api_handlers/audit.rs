@@ -0,0 +1,124 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json as JsonResponse,
+    routing::get,
+    Router,
+};
+use rusqlite::types::Value as SqlValue;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use crate::auth::{AdminUser, Role};
+use crate::db::AppState;
+use super::listing::{self, Page};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+#[derive(Serialize)]
+pub struct AuditEntry {
+    id: i64,
+    actor_kind: String,
+    actor_id: Option<i64>,
+    actor_name: String,
+    action: String,
+    target_id: Option<i64>,
+    client_ip: String,
+    changes: Value,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct AuditLogResponse {
+    entries: Vec<AuditEntry>,
+    total: i64,
+    page: i64,
+    per_page: i64,
+}
+
+/// Filters for the audit log; `from` and `to` are inclusive `YYYY-MM-DD` dates.
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    actor: Option<String>,
+    actor_kind: Option<String>,
+    action: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(list_entries))
+        .with_state(state)
+}
+
+async fn list_entries(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Query(query): Query<AuditQuery>,
+) -> Result<JsonResponse<AuditLogResponse>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let mut conditions = Vec::new();
+    let mut values: Vec<SqlValue> = Vec::new();
+
+    if let Some(actor) = query.actor {
+        conditions.push("actor_name = ?");
+        values.push(SqlValue::Text(actor));
+    }
+    if let Some(actor_kind) = query.actor_kind {
+        conditions.push("actor_kind = ?");
+        values.push(SqlValue::Text(actor_kind));
+    }
+    if let Some(action) = query.action {
+        conditions.push("action = ?");
+        values.push(SqlValue::Text(action));
+    }
+    listing::created_between(query.from.as_deref(), query.to.as_deref(), &mut conditions, &mut values)?;
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    let Page { page, per_page, offset } = listing::page(query.page, query.per_page, DEFAULT_PER_PAGE, MAX_PER_PAGE)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM audit_log {}", where_clause),
+        rusqlite::params_from_iter(values.iter()),
+        |row| row.get(0),
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, actor_kind, actor_id, actor_name, action, target_id, client_ip, changes, created_at
+         FROM audit_log {} ORDER BY id DESC LIMIT ? OFFSET ?",
+        where_clause
+    )).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    values.push(SqlValue::Integer(per_page));
+    values.push(SqlValue::Integer(offset));
+
+    let entries = stmt.query_map(rusqlite::params_from_iter(values.iter()), |row| {
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            actor_kind: row.get(1)?,
+            actor_id: row.get(2)?,
+            actor_name: row.get(3)?,
+            action: row.get(4)?,
+            target_id: row.get(5)?,
+            client_ip: row.get(6)?,
+            changes: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or(Value::Null),
+            created_at: row.get(8)?,
+        })
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter_map(|e| e.ok())
+        .collect();
+
+    Ok(JsonResponse(AuditLogResponse { entries, total, page, per_page }))
+}
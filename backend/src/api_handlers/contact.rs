@@ -1,99 +1,472 @@
 use axum::{
-    extract::{Json, State},
+    extract::{DefaultBodyLimit, Json, State},
     http::StatusCode,
-    response::Json as JsonResponse,
-    routing::post,
+    response::{IntoResponse, Json as JsonResponse, Response},
+    routing::{get, post},
     Router,
 };
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use crate::auth::{self, ClientIp};
+use crate::bayes;
 use crate::db::AppState;
+use crate::email_templates;
+use crate::lockout;
+use crate::mail;
+use crate::outbox;
+use crate::spam;
+use crate::webhooks;
 
+// Missing fields deserialize as empty so they're reported as field errors, not a bare 422
 #[derive(Deserialize)]
 pub struct ContactRequest {
+    #[serde(default)]
     name: String,
+    #[serde(default)]
     email: String,
+    #[serde(default)]
     subject: String,
+    #[serde(default)]
     message: String,
+    // Hidden from people by the form; anything in it came from a bot
+    #[serde(default)]
+    website: String,
+    // From GET /token when the form was rendered
+    #[serde(default)]
+    form_token: String,
+}
+
+#[derive(Serialize)]
+pub struct FormTokenResponse {
+    token: String,
 }
 
 #[derive(Serialize)]
 pub struct ContactResponse {
     success: bool,
     message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+#[derive(Serialize)]
+pub struct FieldError {
+    field: &'static str,
+    code: &'static str,
+    message: String,
 }
 
+// Limits in characters, after trimming
+const MAX_NAME_LEN: usize = 100;
+const MAX_EMAIL_LEN: usize = 254;
+const MAX_SUBJECT_LEN: usize = 200;
+const MAX_MESSAGE_LEN: usize = 5000;
+
+// A person needs a few seconds to fill the form in; a form left open for hours
+// gets a fresh token on reload. Overridable with CONTACT_MIN_FILL_SECS and CONTACT_FORM_MAX_AGE_SECS.
+const DEFAULT_MIN_FILL_SECS: i64 = 3;
+const DEFAULT_FORM_MAX_AGE_SECS: i64 = 2 * 60 * 60;
+const FORM_TOKEN_PURPOSE: &str = "contact-form";
+
+// One acknowledgement per address per day unless CONTACT_AUTOREPLY_COOLDOWN_SECS says otherwise,
+// so the form can't be used to flood someone's inbox
+const DEFAULT_AUTOREPLY_COOLDOWN_SECS: i64 = 24 * 60 * 60;
+// Sender name on mail to submitters unless CONTACT_REPLY_NAME says otherwise
+const DEFAULT_REPLY_FROM_NAME: &str = "Josh Fajardo";
+
 pub fn router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", post(submit_contact))
+        .route("/token", get(form_token))
+        // Far above what the field limits allow, far below axum's 2 MB default
+        .layer(DefaultBodyLimit::max(64 * 1024))
         .with_state(state)
 }
 
+fn env_secs(name: &str, default: i64) -> i64 {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// Issues the signed render timestamp the form sends back as `form_token`.
+async fn form_token(State(state): State<Arc<AppState>>) -> JsonResponse<FormTokenResponse> {
+    let issued_at = chrono::Utc::now().timestamp().to_string();
+    let signature = auth::sign_value(&state.token_secret, FORM_TOKEN_PURPOSE, &issued_at);
+
+    JsonResponse(FormTokenResponse { token: format!("{}.{}", issued_at, signature) })
+}
+
+fn check_form_token(token_secret: &[u8], token: &str) -> Option<FieldError> {
+    let error = |code, message: &str| Some(FieldError { field: "form_token", code, message: message.to_string() });
+
+    let Some((issued_at, signature)) = token.split_once('.') else {
+        return error("invalid", "Please reload the page and try again.");
+    };
+    let Ok(issued_at_secs) = issued_at.parse::<i64>() else {
+        return error("invalid", "Please reload the page and try again.");
+    };
+    if !auth::verify_value(token_secret, FORM_TOKEN_PURPOSE, issued_at, signature) {
+        return error("invalid", "Please reload the page and try again.");
+    }
+
+    let age = chrono::Utc::now().timestamp() - issued_at_secs;
+    if age < env_secs("CONTACT_MIN_FILL_SECS", DEFAULT_MIN_FILL_SECS) {
+        return error("too_fast", "That was quick! Please wait a moment and send again.");
+    }
+    if age > env_secs("CONTACT_FORM_MAX_AGE_SECS", DEFAULT_FORM_MAX_AGE_SECS) {
+        return error("expired", "This form has expired. Please reload the page and try again.");
+    }
+
+    None
+}
+
 async fn submit_contact(
     State(state): State<Arc<AppState>>,
+    ClientIp(client_ip): ClientIp,
     Json(request): Json<ContactRequest>,
-) -> Result<JsonResponse<ContactResponse>, StatusCode> {
+) -> Result<Response, StatusCode> {
+    let token_error = check_form_token(&state.token_secret, &request.form_token);
+
+    let request = match (validate(request), token_error) {
+        (Ok(request), None) => request,
+        (Ok(_), Some(error)) => return Ok(invalid_response(vec![error])),
+        (Err(mut errors), token_error) => {
+            errors.extend(token_error);
+            return Ok(invalid_response(errors));
+        }
+    };
+
     let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    conn.execute(
-        "INSERT INTO contacts (name, email, subject, message) VALUES (?1, ?2, ?3, ?4)",
-        [&request.name, &request.email, &request.subject, &request.message],
+    // Bots get the same answer as everyone else; their messages are just filed as spam
+    let honeypot_filled = !request.website.is_empty();
+    let spam_score = spam::score(&request.name, &request.email, &request.subject, &request.message);
+    let document = bayes::document(&request.name, &request.email, &request.subject, &request.message);
+    let spam_probability = bayes::probability(&conn, &document).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let is_spam = honeypot_filled
+        || spam::is_spam(spam_score)
+        || spam_probability.is_some_and(|p| p >= bayes::probability_threshold());
+    let status = if is_spam { "spam" } else { "inbox" };
+    let message_id = mail::new_message_id();
+
+    // The notification is queued with the submission, so one is never saved without the other
+    let tx = conn.unchecked_transaction().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tx.execute(
+        "INSERT INTO contacts (name, email, subject, message, status, spam_score, spam_probability, message_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![request.name, request.email, request.subject, request.message, status, spam_score, spam_probability, message_id],
     ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let contact_id = tx.last_insert_rowid();
 
-    drop(conn);
+    if is_spam {
+        tracing::info!(
+            "Contact from {} filed as spam (score {}, probability {:?}, honeypot {})",
+            request.email, spam_score, spam_probability, honeypot_filled
+        );
+    } else {
+        let notification = notification_email(&tx, &request, &message_id).map_err(|e| {
+            tracing::error!("Failed to render contact notification: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        outbox::enqueue(&tx, "contact_notification", &notification).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let name = request.name.clone();
-    let email = request.email.clone();
-    let subject = request.subject.clone();
-    let message = request.message.clone();
+        let contact = serde_json::json!({
+            "id": contact_id,
+            "name": request.name,
+            "email": request.email,
+            "subject": request.subject,
+            "message": request.message,
+        });
+        webhooks::enqueue(&tx, "contact.created", &serde_json::json!({ "contact": contact }))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    tokio::spawn(async move {
-        let _ = send_mailgun_email(&name, &email, &subject, &message).await;
-    });
+        if autoreply_enabled() {
+            let recently_acknowledged = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM contacts WHERE lower(email) = lower(?1) AND acknowledged_at >= datetime('now', ?2))",
+                rusqlite::params![request.email, format!("-{} seconds", env_secs("CONTACT_AUTOREPLY_COOLDOWN_SECS", DEFAULT_AUTOREPLY_COOLDOWN_SECS))],
+                |row| row.get::<_, bool>(0),
+            ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            // Besides the per-address cooldown, each client only gets a few acknowledgements
+            // before they're throttled, whichever addresses it gives
+            let autoreply_keys = [lockout::autoreply_key(&client_ip)];
+            let throttled = lockout::retry_after(&tx, &autoreply_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            if recently_acknowledged {
+                tracing::info!("Not acknowledging contact {} from {}: within cooldown", contact_id, request.email);
+            } else if throttled.is_some() {
+                tracing::warn!("Not acknowledging contact {} from {}: {} is throttled", contact_id, request.email, client_ip);
+            } else {
+                lockout::record_failure(&tx, &autoreply_keys).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                let autoreply = autoreply_email(&tx, &request, &message_id).map_err(|e| {
+                    tracing::error!("Failed to render contact acknowledgement: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+                outbox::enqueue(&tx, "contact_autoreply", &autoreply).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                tx.execute("UPDATE contacts SET acknowledged_at = CURRENT_TIMESTAMP WHERE id = ?1", [contact_id])
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            }
+        }
+    }
+
+    tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(JsonResponse(ContactResponse {
         success: true,
         message: "Thank you! Your message has been received.".to_string(),
-    }))
+        errors: Vec::new(),
+    }).into_response())
+}
+
+fn invalid_response(errors: Vec<FieldError>) -> Response {
+    (StatusCode::UNPROCESSABLE_ENTITY, JsonResponse(ContactResponse {
+        success: false,
+        message: "Please correct the highlighted fields.".to_string(),
+        errors,
+    })).into_response()
+}
+
+/// Removes control characters and trims. Single-line fields get spaces in place of
+/// line breaks, which keeps them from smuggling extra headers into the notification email.
+fn clean(value: &str, multiline: bool) -> String {
+    value
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\t' if multiline => Some(c),
+            '\r' if multiline => None,
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Roughly RFC 5321: a dot-atom local part and a hostname with at least two labels.
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+
+    let local_ok = !local.is_empty()
+        && local.len() <= 64
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+/=?^_`{|}~.-".contains(c));
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_ok = labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels.last().is_some_and(|tld| tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic()));
+
+    local_ok && domain_ok
+}
+
+fn check_length(errors: &mut Vec<FieldError>, field: &'static str, value: &str, max: usize) {
+    let len = value.chars().count();
+    if len == 0 {
+        errors.push(FieldError { field, code: "required", message: "This field is required.".to_string() });
+    } else if len > max {
+        errors.push(FieldError {
+            field,
+            code: "too_long",
+            message: format!("Must be at most {} characters.", max),
+        });
+    }
+}
+
+/// Cleans every field and collects all problems at once, so the form can show them together.
+fn validate(request: ContactRequest) -> Result<ContactRequest, Vec<FieldError>> {
+    let request = ContactRequest {
+        name: clean(&request.name, false),
+        email: clean(&request.email, false),
+        subject: clean(&request.subject, false),
+        message: clean(&request.message, true),
+        website: request.website.trim().to_string(),
+        form_token: request.form_token,
+    };
+
+    let mut errors = Vec::new();
+    check_length(&mut errors, "name", &request.name, MAX_NAME_LEN);
+    check_length(&mut errors, "email", &request.email, MAX_EMAIL_LEN);
+    if !request.email.is_empty() && request.email.len() <= MAX_EMAIL_LEN && !is_valid_email(&request.email) {
+        errors.push(FieldError {
+            field: "email",
+            code: "invalid",
+            message: "Enter a valid email address.".to_string(),
+        });
+    }
+    check_length(&mut errors, "subject", &request.subject, MAX_SUBJECT_LEN);
+    check_length(&mut errors, "message", &request.message, MAX_MESSAGE_LEN);
+
+    if errors.is_empty() {
+        Ok(request)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Where contact notifications go, and where replies to our answers should come back to.
+pub fn contact_email() -> String {
+    std::env::var("CONTACT_EMAIL")
+        .unwrap_or_else(|_| "jfajardo7@my.bcit.ca".to_string())
+}
+
+/// The name mail to submitters is sent under.
+pub fn reply_from_name() -> String {
+    std::env::var("CONTACT_REPLY_NAME").unwrap_or_else(|_| DEFAULT_REPLY_FROM_NAME.to_string())
 }
 
-async fn send_mailgun_email(name: &str, email: &str, subject: &str, message: &str) -> Result<(), String> {
-    use reqwest::Client;
+fn autoreply_enabled() -> bool {
+    std::env::var("CONTACT_AUTOREPLY").is_ok_and(|v| v == "true" || v == "1")
+}
 
-    let api_key = std::env::var("MAILGUN_API_KEY")
-        .map_err(|_| "MAILGUN_API_KEY not set")?;
-    let domain = std::env::var("MAILGUN_DOMAIN")
-        .map_err(|_| "MAILGUN_DOMAIN not set")?;
-    let recipient = std::env::var("CONTACT_EMAIL")
-        .unwrap_or_else(|_| "jfajardo7@my.bcit.ca".to_string());
+// Sent in reply to the submission's Message-ID, so a later answer lands in the same thread.
+// The message itself isn't echoed back, or the form would send anyone's text to any address.
+fn autoreply_email(conn: &Connection, request: &ContactRequest, message_id: &str) -> Result<mail::Email, String> {
+    let data = serde_json::json!({
+        "name": request.name,
+        "email": request.email,
+        "subject": request.subject,
+    });
+    let rendered = email_templates::render(conn, "contact_autoreply", &data)?;
 
-    tracing::info!("Sending email via Mailgun: domain={}, to={}", domain, recipient);
+    Ok(mail::Email {
+        from_name: reply_from_name(),
+        to: request.email.clone(),
+        subject: rendered.subject,
+        text: rendered.text,
+        html: Some(rendered.html),
+        headers: vec![
+            ("Message-Id".to_string(), mail::new_message_id()),
+            ("In-Reply-To".to_string(), message_id.to_string()),
+            ("References".to_string(), message_id.to_string()),
+            ("Reply-To".to_string(), contact_email()),
+            // Marks it as automatic (RFC 3834) so well-behaved autoresponders don't answer it
+            ("Auto-Submitted".to_string(), "auto-replied".to_string()),
+        ],
+    })
+}
 
-    let client = Client::new();
-    let url = format!("https://api.mailgun.net/v3/{}/messages", domain);
+// The notification carries the submission's Message-ID, so replies sent later thread under it
+fn notification_email(conn: &Connection, request: &ContactRequest, message_id: &str) -> Result<mail::Email, String> {
+    let rendered = email_templates::render(conn, "contact_notification", &template_data(request))?;
 
-    let form_params = [
-        ("from", format!("Portfolio Contact <mailgun@{}>", domain)),
-        ("to", recipient.clone()),
-        ("subject", format!("{} - {}", subject, name)),
-        ("text", format!("From: {} <{}>\n\n{}", name, email, message)),
-    ];
+    Ok(mail::Email {
+        from_name: "Portfolio Contact".to_string(),
+        to: contact_email(),
+        subject: rendered.subject,
+        text: rendered.text,
+        html: Some(rendered.html),
+        headers: vec![("Message-Id".to_string(), message_id.to_string())],
+    })
+}
 
-    let response = client
-        .post(&url)
-        .basic_auth("api", Some(&api_key))
-        .form(&form_params)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send email: {}", e))?;
+fn template_data(request: &ContactRequest) -> serde_json::Value {
+    serde_json::json!({
+        "name": request.name,
+        "email": request.email,
+        "subject": request.subject,
+        "message": request.message,
+    })
+}
 
-    let status = response.status();
-    let body = response.text().await.unwrap_or_default();
-    tracing::info!("Mailgun response: status={}, body={}", status, body);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if !status.is_success() {
-        return Err(format!("Mailgun error: {} - {}", status, body));
+    const SECRET: &[u8] = b"test secret";
+
+    fn request(name: &str, email: &str, subject: &str, message: &str) -> ContactRequest {
+        ContactRequest {
+            name: name.to_string(),
+            email: email.to_string(),
+            subject: subject.to_string(),
+            message: message.to_string(),
+            website: String::new(),
+            form_token: String::new(),
+        }
+    }
+
+    fn form_token_issued(secs_ago: i64) -> String {
+        let issued_at = (chrono::Utc::now().timestamp() - secs_ago).to_string();
+        format!("{}.{}", issued_at, auth::sign_value(SECRET, FORM_TOKEN_PURPOSE, &issued_at))
+    }
+
+    fn codes(errors: &[FieldError]) -> Vec<(&str, &str)> {
+        errors.iter().map(|e| (e.field, e.code)).collect()
+    }
+
+    #[test]
+    fn accepts_ordinary_addresses() {
+        for email in ["jane@example.com", "jane.doe+portfolio@mail.example.co.uk", "o'brien@example.ie", "a-b_c@sub-domain.example.org"] {
+            assert!(is_valid_email(email), "{}", email);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_addresses() {
+        for email in [
+            "", "jane", "@example.com", "jane@", "jane@localhost", "jane@example.c", "jane@example.123",
+            ".jane@example.com", "jane.@example.com", "ja..ne@example.com", "jane doe@example.com",
+            "jane@-example.com", "jane@example-.com", "jane@exa_mple.com", "jane@example..com",
+            "<jane>@example.com", "jane@example.com\nBcc: victim@example.com",
+        ] {
+            assert!(!is_valid_email(email), "{:?}", email);
+        }
+
+        assert!(!is_valid_email(&format!("{}@example.com", "a".repeat(65))));
+        assert!(!is_valid_email(&format!("jane@{}.com", "a".repeat(64))));
     }
 
-    Ok(())
+    #[test]
+    fn line_breaks_in_single_line_fields_are_flattened() {
+        let cleaned = validate(request("Jane\r\nBcc: victim@example.com", "jane@example.com", "Hi\nthere", "Line one\r\nLine two\u{0}"))
+            .unwrap_or_else(|_| panic!("request is valid once cleaned"));
+
+        assert_eq!(cleaned.name, "Jane  Bcc: victim@example.com");
+        assert_eq!(cleaned.subject, "Hi there");
+        assert_eq!(cleaned.message, "Line one\nLine two");
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let Err(errors) = validate(request("  ", "not an address", &"s".repeat(MAX_SUBJECT_LEN + 1), "\u{7}")) else {
+            panic!("request should be rejected");
+        };
+
+        assert_eq!(codes(&errors), [
+            ("name", "required"),
+            ("email", "invalid"),
+            ("subject", "too_long"),
+            ("message", "required"),
+        ]);
+    }
+
+    #[test]
+    fn form_token_must_be_signed_and_in_date() {
+        assert!(check_form_token(SECRET, &form_token_issued(60)).is_none());
+
+        let code = |token: &str| check_form_token(SECRET, token).map(|e| e.code);
+        assert_eq!(code(&form_token_issued(0)), Some("too_fast"));
+        assert_eq!(code(&form_token_issued(DEFAULT_FORM_MAX_AGE_SECS + 60)), Some("expired"));
+        assert_eq!(code(""), Some("invalid"));
+        assert_eq!(code("not-a-number.signature"), Some("invalid"));
+
+        // Moving the timestamp of a valid token, or signing it with another key, breaks the signature
+        let token = form_token_issued(60);
+        let (_, signature) = token.split_once('.').unwrap();
+        let backdated = format!("{}.{}", chrono::Utc::now().timestamp() - 120, signature);
+        assert_eq!(code(&backdated), Some("invalid"));
+        assert_eq!(check_form_token(b"another secret", &form_token_issued(60)).map(|e| e.code), Some("invalid"));
+    }
 }
@@ -0,0 +1,813 @@
+use axum::{
+    body::Body,
+    extract::{Json, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json as JsonResponse, Response},
+    routing::{delete, get, post},
+    Router,
+};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::audit::{self, AuditEvent};
+use crate::auth::{ClientIp, Principal, Scope};
+use crate::bayes::{self, Label};
+use crate::db::AppState;
+use crate::email_templates;
+use crate::export::{self, Format};
+use crate::mail;
+use crate::outbox;
+use super::contact;
+use super::listing::{self, Page};
+
+#[derive(Serialize)]
+pub struct ContactSubmission {
+    id: i64,
+    name: String,
+    email: String,
+    subject: String,
+    message: String,
+    created_at: String,
+    read: bool,
+    archived_at: Option<String>,
+    acknowledged_at: Option<String>,
+    status: String,
+    spam_score: f64,
+    spam_probability: Option<f64>,
+    spam_label: Option<String>,
+    replies: i64,
+}
+
+#[derive(Serialize)]
+pub struct ContactReply {
+    id: i64,
+    message_id: String,
+    in_reply_to: String,
+    subject: String,
+    body: String,
+    sent_by: String,
+    created_at: String,
+}
+
+/// A submission followed by every reply sent to it, oldest first.
+#[derive(Serialize)]
+pub struct ContactThread {
+    contact: ContactSubmission,
+    replies: Vec<ContactReply>,
+}
+
+#[derive(Deserialize)]
+pub struct ReplyRequest {
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Deserialize)]
+pub struct MarkSpamRequest {
+    spam: bool,
+}
+
+#[derive(Serialize)]
+pub struct ContactsResponse {
+    contacts: Vec<ContactSubmission>,
+    total: i64,
+    page: i64,
+    per_page: i64,
+}
+
+/// Filters for the contact list. `from` and `to` are inclusive `YYYY-MM-DD` dates,
+/// `subject` matches part of the subject and `q` searches name, email and message.
+#[derive(Deserialize)]
+pub struct ContactQuery {
+    read: Option<bool>,
+    archived: Option<bool>,
+    status: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    subject: Option<String>,
+    q: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+const DEFAULT_CONTACTS_PER_PAGE: i64 = 25;
+const MAX_CONTACTS_PER_PAGE: i64 = 100;
+// Rows read per database lock while streaming an export
+const EXPORT_BATCH_SIZE: i64 = 500;
+const MAX_REPLY_LENGTH: usize = 10_000;
+
+const CONTACT_COLUMNS: &str = "id, name, email, subject, message, created_at, read, archived_at, acknowledged_at, status,
+    spam_score, spam_probability, spam_label, (SELECT COUNT(*) FROM contact_replies WHERE contact_id = contacts.id)";
+
+/// What a single or bulk contact action does.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContactAction {
+    MarkRead,
+    MarkUnread,
+    Archive,
+    Unarchive,
+    Delete,
+}
+
+impl ContactAction {
+    fn name(&self) -> &'static str {
+        match self {
+            ContactAction::MarkRead => "mark_read",
+            ContactAction::MarkUnread => "mark_unread",
+            ContactAction::Archive => "archive",
+            ContactAction::Unarchive => "unarchive",
+            ContactAction::Delete => "delete",
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            ContactAction::MarkRead => "UPDATE contacts SET read = 1 WHERE id = ?1",
+            ContactAction::MarkUnread => "UPDATE contacts SET read = 0 WHERE id = ?1",
+            ContactAction::Archive => "UPDATE contacts SET archived_at = COALESCE(archived_at, CURRENT_TIMESTAMP) WHERE id = ?1",
+            ContactAction::Unarchive => "UPDATE contacts SET archived_at = NULL WHERE id = ?1",
+            ContactAction::Delete => "DELETE FROM contacts WHERE id = ?1",
+        }
+    }
+}
+
+/// Either explicit `ids` or a `filter` (the list filters, minus paging), not both.
+/// A filter without any conditions matches every contact, so it also needs `all: true`.
+#[derive(Deserialize)]
+pub struct BulkActionRequest {
+    action: ContactAction,
+    ids: Option<Vec<i64>>,
+    filter: Option<ContactQuery>,
+    #[serde(default)]
+    all: bool,
+}
+
+#[derive(Serialize)]
+pub struct ActionResponse {
+    affected: usize,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(list_contacts))
+        .route("/bulk", post(bulk_action))
+        .route("/export/:format", get(export_contacts))
+        .route("/:id", delete(delete_contact))
+        .route("/:id/read", post(mark_contact_read))
+        .route("/:id/unread", post(mark_contact_unread))
+        .route("/:id/archive", post(archive_contact))
+        .route("/:id/unarchive", post(unarchive_contact))
+        .route("/:id/spam", post(mark_contact_spam))
+        .route("/:id/replies", get(get_thread).post(reply_to_contact))
+        .with_state(state)
+}
+
+/// Escapes LIKE wildcards so a search for "50%" means the literal text.
+fn like_pattern(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Builds the WHERE clause and parameters for a [`ContactQuery`], rejecting malformed dates with 400.
+pub fn contact_filter(query: &ContactQuery) -> Result<(String, Vec<SqlValue>), StatusCode> {
+    let mut conditions = Vec::new();
+    let mut values: Vec<SqlValue> = Vec::new();
+
+    if let Some(read) = query.read {
+        conditions.push("read = ?");
+        values.push(SqlValue::Integer(read as i64));
+    }
+    if let Some(archived) = query.archived {
+        conditions.push(if archived { "archived_at IS NOT NULL" } else { "archived_at IS NULL" });
+    }
+    if let Some(status) = &query.status {
+        conditions.push("status = ?");
+        values.push(SqlValue::Text(status.clone()));
+    }
+    listing::created_between(query.from.as_deref(), query.to.as_deref(), &mut conditions, &mut values)?;
+    if let Some(subject) = query.subject.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        conditions.push("subject LIKE ? ESCAPE '\\'");
+        values.push(SqlValue::Text(like_pattern(subject)));
+    }
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        conditions.push("(name LIKE ? ESCAPE '\\' OR email LIKE ? ESCAPE '\\' OR message LIKE ? ESCAPE '\\')");
+        let pattern = like_pattern(q);
+        values.extend(std::iter::repeat_n(SqlValue::Text(pattern), 3));
+    }
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    Ok((where_clause, values))
+}
+
+async fn list_contacts(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    Query(query): Query<ContactQuery>,
+) -> Result<JsonResponse<ContactsResponse>, StatusCode> {
+    caller.require(Scope::ContactsRead)?;
+
+    let (where_clause, mut values) = contact_filter(&query)?;
+    let Page { page, per_page, offset } =
+        listing::page(query.page, query.per_page, DEFAULT_CONTACTS_PER_PAGE, MAX_CONTACTS_PER_PAGE)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM contacts {}", where_clause),
+        rusqlite::params_from_iter(values.iter()),
+        |row| row.get(0),
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM contacts {} ORDER BY created_at DESC, id DESC LIMIT ? OFFSET ?",
+        CONTACT_COLUMNS, where_clause
+    )).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    values.push(SqlValue::Integer(per_page));
+    values.push(SqlValue::Integer(offset));
+
+    let contacts = stmt.query_map(rusqlite::params_from_iter(values.iter()), contact_from_row)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let contact_list: Vec<ContactSubmission> = contacts.filter_map(|c| c.ok()).collect();
+
+    Ok(JsonResponse(ContactsResponse { contacts: contact_list, total, page, per_page }))
+}
+
+/// Streams every contact matching the list filters as CSV, JSON Lines or mbox, oldest
+/// first. Rows are read in batches by id, so neither the export nor the database lock
+/// is held for the whole response; `page` and `per_page` are ignored.
+async fn export_contacts(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    Path(format): Path<String>,
+    Query(query): Query<ContactQuery>,
+) -> Result<Response, StatusCode> {
+    caller.require(Scope::ContactsRead)?;
+
+    let format = Format::parse(&format).ok_or(StatusCode::NOT_FOUND)?;
+    let (where_clause, values) = contact_filter(&query)?;
+
+    let sql = format!(
+        "SELECT {} FROM contacts {} {} id > ? ORDER BY id LIMIT ?",
+        export::COLUMNS,
+        where_clause,
+        if where_clause.is_empty() { "WHERE" } else { "AND" },
+    );
+
+    // (last id written, whether the header has gone out, whether the rows ran out)
+    let chunks = futures_util::stream::unfold((0i64, false, false), move |(after_id, started, done)| {
+        let state = state.clone();
+        let sql = sql.clone();
+        let values = values.clone();
+        async move {
+            if done {
+                return None;
+            }
+
+            let mut chunk = if started { String::new() } else { format.header().to_string() };
+            let entries = match export_batch(&state, &sql, values, after_id) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    tracing::error!("Contact export failed after id {}: {}", after_id, e);
+                    return Some((Err(std::io::Error::other(e)), (after_id, true, true)));
+                }
+            };
+
+            let last_id = entries.last().map(|entry| entry.id()).unwrap_or(after_id);
+            let finished = (entries.len() as i64) < EXPORT_BATCH_SIZE;
+            for entry in &entries {
+                format.write(entry, &mut chunk);
+            }
+
+            Some((Ok(chunk), (last_id, true, finished)))
+        }
+    });
+
+    let filename = format!("contacts-{}.{}", chrono::Utc::now().format("%Y%m%d"), format.extension());
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(chunks),
+    ).into_response())
+}
+
+/// The next batch of export rows after `after_id`.
+fn export_batch(
+    state: &AppState,
+    sql: &str,
+    mut values: Vec<SqlValue>,
+    after_id: i64,
+) -> Result<Vec<export::Entry>, String> {
+    values.push(SqlValue::Integer(after_id));
+    values.push(SqlValue::Integer(EXPORT_BATCH_SIZE));
+
+    let conn = state.conn.lock().map_err(|_| "database lock poisoned".to_string())?;
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    let entries = stmt.query_map(rusqlite::params_from_iter(values.iter()), export::Entry::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+/// Maps a row selected with `CONTACT_COLUMNS`.
+fn contact_from_row(row: &Row) -> rusqlite::Result<ContactSubmission> {
+    Ok(ContactSubmission {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        email: row.get(2)?,
+        subject: row.get(3)?,
+        message: row.get(4)?,
+        created_at: row.get(5)?,
+        read: row.get::<_, i64>(6)? != 0,
+        archived_at: row.get(7)?,
+        acknowledged_at: row.get(8)?,
+        status: row.get(9)?,
+        spam_score: row.get(10)?,
+        spam_probability: row.get(11)?,
+        spam_label: row.get(12)?,
+        replies: row.get(13)?,
+    })
+}
+
+fn reply_from_row(row: &Row) -> rusqlite::Result<ContactReply> {
+    Ok(ContactReply {
+        id: row.get(0)?,
+        message_id: row.get(1)?,
+        in_reply_to: row.get(2)?,
+        subject: row.get(3)?,
+        body: row.get(4)?,
+        sent_by: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+async fn get_thread(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<ContactThread>, StatusCode> {
+    caller.require(Scope::ContactsRead)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let contact = conn.query_row(
+        &format!("SELECT {} FROM contacts WHERE id = ?1", CONTACT_COLUMNS),
+        [id],
+        contact_from_row,
+    ).optional()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, message_id, in_reply_to, subject, body, sent_by, created_at
+         FROM contact_replies WHERE contact_id = ?1 ORDER BY id"
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let replies = stmt.query_map([id], reply_from_row)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(JsonResponse(ContactThread { contact, replies }))
+}
+
+/// Stores a reply in the thread and queues it for the submitter in the same transaction,
+/// so a reply is never sent without being recorded or recorded without being sent. The
+/// reply answers the latest message in the thread and references all of them, so mail
+/// clients on both ends group it with the original submission; Reply-To brings answers
+/// back to CONTACT_EMAIL.
+async fn reply_to_contact(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+    Json(request): Json<ReplyRequest>,
+) -> Result<JsonResponse<ContactReply>, StatusCode> {
+    caller.require(Scope::ContactsWrite)?;
+
+    let body = request.message.replace('\r', "").trim().to_string();
+    if body.is_empty() || body.chars().count() > MAX_REPLY_LENGTH {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (name, email, subject, root_id): (String, String, String, Option<String>) = conn.query_row(
+        "SELECT name, email, subject, message_id FROM contacts WHERE id = ?1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    ).optional()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let tx = conn.unchecked_transaction().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let root_id = match root_id {
+        Some(root_id) => root_id,
+        None => {
+            let root_id = mail::new_message_id();
+            tx.execute("UPDATE contacts SET message_id = ?1 WHERE id = ?2", rusqlite::params![root_id, id])
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            root_id
+        }
+    };
+
+    let mut references = vec![root_id];
+    {
+        let mut stmt = tx.prepare("SELECT message_id FROM contact_replies WHERE contact_id = ?1 ORDER BY id")
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        references.extend(
+            stmt.query_map([id], |row| row.get(0))
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .filter_map(|r| r.ok()),
+        );
+    }
+
+    let message_id = mail::new_message_id();
+    let in_reply_to = references.last().cloned().unwrap_or_default();
+    let subject = if subject.to_lowercase().starts_with("re:") { subject } else { format!("Re: {}", subject) };
+
+    tx.execute(
+        "INSERT INTO contact_replies (contact_id, message_id, in_reply_to, subject, body, sent_by)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![id, message_id, in_reply_to, subject, body, caller.actor.name()],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let reply_id = tx.last_insert_rowid();
+
+    let rendered = email_templates::render(&tx, "contact_reply", &serde_json::json!({
+        "name": name,
+        "subject": subject,
+        "message": body,
+    })).map_err(|e| {
+        tracing::error!("Failed to render reply to contact {}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    outbox::enqueue(&tx, "contact_reply", &mail::Email {
+        from_name: contact::reply_from_name(),
+        to: email,
+        subject: rendered.subject,
+        text: rendered.text,
+        html: Some(rendered.html),
+        headers: vec![
+            ("Message-Id".to_string(), message_id.clone()),
+            ("In-Reply-To".to_string(), in_reply_to),
+            ("References".to_string(), references.join(" ")),
+            ("Reply-To".to_string(), contact::contact_email()),
+        ],
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Answering a message means it's been read
+    tx.execute("UPDATE contacts SET read = 1 WHERE id = ?1", [id])
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&tx, AuditEvent {
+        actor: &caller.actor,
+        action: "contact.reply",
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: None,
+        after: Some(serde_json::json!({ "reply_id": reply_id, "message_id": message_id })),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let reply = conn.query_row(
+        "SELECT id, message_id, in_reply_to, subject, body, sent_by, created_at FROM contact_replies WHERE id = ?1",
+        [reply_id],
+        reply_from_row,
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(reply))
+}
+
+/// The audited state of a contact; audit entries store what changed between two snapshots.
+fn contact_snapshot(conn: &Connection, id: i64) -> rusqlite::Result<serde_json::Value> {
+    conn.query_row(
+        "SELECT name, email, subject, read, archived_at, status FROM contacts WHERE id = ?1",
+        [id],
+        |row| Ok(serde_json::json!({
+            "name": row.get::<_, String>(0)?,
+            "email": row.get::<_, String>(1)?,
+            "subject": row.get::<_, String>(2)?,
+            "read": row.get::<_, i64>(3)? != 0,
+            "archived_at": row.get::<_, Option<String>>(4)?,
+            "status": row.get::<_, String>(5)?,
+        })),
+    )
+}
+
+/// Applies `action` to one contact. A deleted contact's spam/ham mark is unlearned first,
+/// so the filter doesn't keep statistics for messages that no longer exist.
+fn apply_action(conn: &Connection, id: i64, action: &ContactAction) -> rusqlite::Result<usize> {
+    if let ContactAction::Delete = action {
+        let marked: Option<(String, Option<String>)> = conn.query_row(
+            "SELECT name, email, subject, message, spam_label FROM contacts WHERE id = ?1",
+            [id],
+            |row| Ok((
+                bayes::document(&row.get::<_, String>(0)?, &row.get::<_, String>(1)?, &row.get::<_, String>(2)?, &row.get::<_, String>(3)?),
+                row.get(4)?,
+            )),
+        ).optional()?;
+
+        if let Some((document, Some(label))) = marked {
+            if let Some(label) = Label::parse(&label) {
+                bayes::learn(conn, &document, label, -1)?;
+            }
+        }
+    }
+
+    conn.execute(action.sql(), [id])
+}
+
+/// Applies `action` to one contact, answering 404 if it doesn't exist.
+fn apply_to_one(
+    state: &AppState,
+    caller: &Principal,
+    client_ip: &str,
+    id: i64,
+    action: ContactAction,
+) -> Result<JsonResponse<ActionResponse>, StatusCode> {
+    caller.require(Scope::ContactsWrite)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let before = contact_snapshot(&conn, id).map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let tx = conn.unchecked_transaction().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let affected = apply_action(&tx, id, &action).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let after = match action {
+        ContactAction::Delete => None,
+        _ => Some(contact_snapshot(&tx, id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?),
+    };
+
+    audit::record(&tx, AuditEvent {
+        actor: &caller.actor,
+        action: &format!("contact.{}", action.name()),
+        target_id: Some(id),
+        client_ip,
+        before: Some(before),
+        after,
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(ActionResponse { affected }))
+}
+
+async fn mark_contact_read(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<ActionResponse>, StatusCode> {
+    apply_to_one(&state, &caller, &client_ip, id, ContactAction::MarkRead)
+}
+
+async fn mark_contact_unread(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<ActionResponse>, StatusCode> {
+    apply_to_one(&state, &caller, &client_ip, id, ContactAction::MarkUnread)
+}
+
+async fn archive_contact(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<ActionResponse>, StatusCode> {
+    apply_to_one(&state, &caller, &client_ip, id, ContactAction::Archive)
+}
+
+async fn unarchive_contact(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<ActionResponse>, StatusCode> {
+    apply_to_one(&state, &caller, &client_ip, id, ContactAction::Unarchive)
+}
+
+async fn delete_contact(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<ActionResponse>, StatusCode> {
+    apply_to_one(&state, &caller, &client_ip, id, ContactAction::Delete)
+}
+
+/// Applies one action to a list of ids or to everything matching a filter, in one transaction.
+/// A list naming any contact that doesn't exist is rejected with 404 and changes nothing.
+async fn bulk_action(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
+    Json(request): Json<BulkActionRequest>,
+) -> Result<JsonResponse<ActionResponse>, StatusCode> {
+    caller.require(Scope::ContactsWrite)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let ids: Vec<i64> = match (request.ids, &request.filter) {
+        (Some(mut ids), None) => {
+            ids.sort_unstable();
+            ids.dedup();
+
+            let mut stmt = conn.prepare("SELECT 1 FROM contacts WHERE id = ?1")
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            for id in &ids {
+                if !stmt.exists([id]).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+                    return Err(StatusCode::NOT_FOUND);
+                }
+            }
+            ids
+        }
+        (None, Some(filter)) => {
+            let (where_clause, values) = contact_filter(filter)?;
+            if where_clause.is_empty() && !request.all {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            let mut stmt = conn.prepare(&format!("SELECT id FROM contacts {}", where_clause))
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            let ids = stmt.query_map(rusqlite::params_from_iter(values.iter()), |row| row.get(0))
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .filter_map(|id| id.ok())
+                .collect();
+            ids
+        }
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let tx = conn.unchecked_transaction().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut affected = 0;
+    for id in &ids {
+        affected += apply_action(&tx, *id, &request.action).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    audit::record(&tx, AuditEvent {
+        actor: &caller.actor,
+        action: &format!("contact.bulk_{}", request.action.name()),
+        target_id: None,
+        client_ip: &client_ip,
+        before: None,
+        after: Some(serde_json::json!({ "ids": ids, "affected": affected })),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(ActionResponse { affected }))
+}
+
+/// Records an admin's spam/not-spam verdict, moves the contact accordingly and
+/// teaches the filter. Changing an earlier verdict unlearns it first.
+async fn mark_contact_spam(
+    State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+    Json(request): Json<MarkSpamRequest>,
+) -> Result<StatusCode, StatusCode> {
+    caller.require(Scope::ContactsWrite)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (document, status, previous): (String, String, Option<String>) = conn.query_row(
+        "SELECT name, email, subject, message, status, spam_label FROM contacts WHERE id = ?1",
+        [id],
+        |row| Ok((
+            bayes::document(&row.get::<_, String>(0)?, &row.get::<_, String>(1)?, &row.get::<_, String>(2)?, &row.get::<_, String>(3)?),
+            row.get(4)?,
+            row.get(5)?,
+        )),
+    ).map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let label = if request.spam { Label::Spam } else { Label::Ham };
+    let previous_label = previous.as_deref().and_then(Label::parse);
+
+    let tx = conn.unchecked_transaction().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if previous_label != Some(label) {
+        if let Some(previous_label) = previous_label {
+            bayes::learn(&tx, &document, previous_label, -1).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+        bayes::learn(&tx, &document, label, 1).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    let new_status = if request.spam { "spam" } else { "inbox" };
+    tx.execute(
+        "UPDATE contacts SET spam_label = ?1, status = ?2 WHERE id = ?3",
+        rusqlite::params![label.as_str(), new_status, id],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&tx, AuditEvent {
+        actor: &caller.actor,
+        action: if request.spam { "contact.mark_spam" } else { "contact.mark_ham" },
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: Some(serde_json::json!({ "status": status, "spam_label": previous })),
+        after: Some(serde_json::json!({ "status": new_status, "spam_label": label.as_str() })),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db() -> Connection {
+        let conn = AppState::migrate(Connection::open_in_memory().unwrap()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO contacts (name, email, subject, message, created_at, read, status) VALUES
+                ('Jane', 'jane@example.com', 'Project enquiry', 'Are you free in May?', '2024-05-01 09:00:00', 0, 'inbox'),
+                ('Joe', 'joe@example.com', '50% off', 'Save_big on everything', '2024-05-02 23:59:59', 1, 'spam'),
+                ('Ann', 'ann@example.org', 'Hello', 'Loved the portfolio', '2024-05-03 00:00:00', 1, 'inbox');",
+        ).unwrap();
+        conn
+    }
+
+    fn matching(conn: &Connection, query: serde_json::Value) -> Result<Vec<i64>, StatusCode> {
+        let query: ContactQuery = serde_json::from_value(query).unwrap();
+        let (where_clause, values) = contact_filter(&query)?;
+        let mut stmt = conn.prepare(&format!("SELECT id FROM contacts {} ORDER BY id", where_clause)).unwrap();
+        let ids = stmt.query_map(rusqlite::params_from_iter(values.iter()), |row| row.get(0)).unwrap();
+        Ok(ids.map(|id| id.unwrap()).collect())
+    }
+
+    #[test]
+    fn no_filters_match_everything() {
+        let conn = db();
+        assert_eq!(matching(&conn, serde_json::json!({})), Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn filters_combine() {
+        let conn = db();
+        assert_eq!(matching(&conn, serde_json::json!({ "read": true })), Ok(vec![2, 3]));
+        assert_eq!(matching(&conn, serde_json::json!({ "read": true, "status": "inbox" })), Ok(vec![3]));
+        assert_eq!(matching(&conn, serde_json::json!({ "subject": "enquiry" })), Ok(vec![1]));
+        assert_eq!(matching(&conn, serde_json::json!({ "q": "EXAMPLE.ORG" })), Ok(vec![3]));
+    }
+
+    #[test]
+    fn date_range_includes_whole_days() {
+        let conn = db();
+        assert_eq!(matching(&conn, serde_json::json!({ "from": "2024-05-02", "to": "2024-05-02" })), Ok(vec![2]));
+        assert_eq!(matching(&conn, serde_json::json!({ "from": "2024-05-02" })), Ok(vec![2, 3]));
+    }
+
+    #[test]
+    fn malformed_dates_are_rejected() {
+        let conn = db();
+        assert_eq!(matching(&conn, serde_json::json!({ "from": "02/05/2024" })), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(matching(&conn, serde_json::json!({ "to": "2024-02-30" })), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn wildcards_in_searches_are_literal() {
+        let conn = db();
+        assert_eq!(matching(&conn, serde_json::json!({ "subject": "50%" })), Ok(vec![2]));
+        assert_eq!(matching(&conn, serde_json::json!({ "q": "e_b" })), Ok(vec![2]));
+        assert_eq!(matching(&conn, serde_json::json!({ "subject": "%" })), Ok(vec![2]));
+        assert_eq!(matching(&conn, serde_json::json!({ "q": "_" })), Ok(vec![2]));
+    }
+
+    #[test]
+    fn deleting_a_marked_contact_unlearns_it() {
+        let conn = db();
+        let document = conn.query_row(
+            "SELECT name, email, subject, message FROM contacts WHERE id = 2",
+            [],
+            |row| Ok(bayes::document(&row.get::<_, String>(0)?, &row.get::<_, String>(1)?, &row.get::<_, String>(2)?, &row.get::<_, String>(3)?)),
+        ).unwrap();
+        bayes::learn(&conn, &document, Label::Spam, 1).unwrap();
+        conn.execute("UPDATE contacts SET spam_label = 'spam' WHERE id = 2", []).unwrap();
+
+        assert_eq!(apply_action(&conn, 2, &ContactAction::Delete).unwrap(), 1);
+
+        let tokens: i64 = conn.query_row("SELECT COUNT(*) FROM spam_tokens", [], |row| row.get(0)).unwrap();
+        let documents: i64 = conn.query_row("SELECT COALESCE(SUM(documents), 0) FROM spam_documents", [], |row| row.get(0)).unwrap();
+        assert_eq!((tokens, documents), (0, 0));
+    }
+}
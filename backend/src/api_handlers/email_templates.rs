@@ -0,0 +1,230 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json as JsonResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use crate::audit::{self, AuditEvent};
+use crate::auth::{Actor, AdminUser, ClientIp, Role};
+use crate::db::AppState;
+use crate::email_templates::{self, Definition, Parts};
+
+// Per part; real templates are a few kilobytes
+const MAX_PART_LEN: usize = 100 * 1024;
+
+#[derive(Serialize)]
+pub struct TemplateSummary {
+    name: &'static str,
+    description: &'static str,
+    variables: &'static [&'static str],
+    customized: bool,
+    updated_by: Option<String>,
+    updated_at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TemplatesResponse {
+    templates: Vec<TemplateSummary>,
+}
+
+#[derive(Serialize)]
+pub struct TemplateDetail {
+    #[serde(flatten)]
+    summary: TemplateSummary,
+    #[serde(flatten)]
+    parts: Parts,
+}
+
+/// A draft to render instead of the saved template; missing parts come from the saved
+/// one. `data` overrides individual sample variables.
+#[derive(Deserialize, Default)]
+pub struct PreviewRequest {
+    subject: Option<String>,
+    text: Option<String>,
+    html: Option<String>,
+    data: Option<serde_json::Map<String, Value>>,
+}
+
+#[derive(Serialize)]
+pub struct TemplateError {
+    error: String,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(list_templates))
+        .route("/:name", get(get_template).put(update_template).delete(reset_template))
+        .route("/:name/preview", post(preview_template))
+        .with_state(state)
+}
+
+fn find(name: &str) -> Result<&'static Definition, StatusCode> {
+    email_templates::definition(name).ok_or(StatusCode::NOT_FOUND)
+}
+
+fn summary(conn: &Connection, definition: &'static Definition) -> rusqlite::Result<TemplateSummary> {
+    let updated: Option<(String, String)> = conn.query_row(
+        "SELECT updated_by, updated_at FROM email_templates WHERE name = ?1",
+        [definition.name],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
+
+    Ok(TemplateSummary {
+        name: definition.name,
+        description: definition.description,
+        variables: definition.variables,
+        customized: updated.is_some(),
+        updated_by: updated.as_ref().map(|(by, _)| by.clone()),
+        updated_at: updated.map(|(_, at)| at),
+    })
+}
+
+fn detail(conn: &Connection, definition: &'static Definition) -> rusqlite::Result<TemplateDetail> {
+    let parts = email_templates::stored(conn, definition.name)?.unwrap_or_else(|| definition.defaults());
+    Ok(TemplateDetail { summary: summary(conn, definition)?, parts })
+}
+
+fn template_error(error: String) -> Response {
+    (StatusCode::UNPROCESSABLE_ENTITY, JsonResponse(TemplateError { error })).into_response()
+}
+
+async fn list_templates(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+) -> Result<JsonResponse<TemplatesResponse>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let templates = email_templates::DEFINITIONS
+        .iter()
+        .map(|definition| summary(&conn, definition))
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(TemplatesResponse { templates }))
+}
+
+async fn get_template(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(name): Path<String>,
+) -> Result<JsonResponse<TemplateDetail>, StatusCode> {
+    admin.require(Role::Owner)?;
+    let definition = find(&name)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    detail(&conn, definition)
+        .map(JsonResponse)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Saves an override after checking that every part renders against the sample data;
+/// a template that doesn't gets 422 with the renderer's error.
+async fn update_template(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    ClientIp(client_ip): ClientIp,
+    Path(name): Path<String>,
+    Json(parts): Json<Parts>,
+) -> Result<Response, StatusCode> {
+    admin.require(Role::Owner)?;
+    let definition = find(&name)?;
+
+    if [&parts.subject, &parts.text, &parts.html].iter().any(|part| part.len() > MAX_PART_LEN) {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    if let Err(error) = email_templates::render_parts(definition.name, &parts, &definition.sample_data()) {
+        return Ok(template_error(error));
+    }
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let before = detail(&conn, definition).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    email_templates::save(&conn, definition.name, &parts, &admin.username)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&conn, AuditEvent {
+        actor: &Actor::Admin { id: admin.id, username: admin.username.clone() },
+        action: "email_template.update",
+        target_id: None,
+        client_ip: &client_ip,
+        before: Some(serde_json::json!({ "name": definition.name, "parts": before.parts })),
+        after: Some(serde_json::json!({ "name": definition.name, "parts": parts })),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let updated = detail(&conn, definition).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(updated).into_response())
+}
+
+/// Removes the override, going back to the bundled default.
+async fn reset_template(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    ClientIp(client_ip): ClientIp,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    admin.require(Role::Owner)?;
+    let definition = find(&name)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let before = email_templates::stored(&conn, definition.name).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some(before) = before else {
+        return Ok(StatusCode::NO_CONTENT);
+    };
+
+    email_templates::reset(&conn, definition.name).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&conn, AuditEvent {
+        actor: &Actor::Admin { id: admin.id, username: admin.username.clone() },
+        action: "email_template.reset",
+        target_id: None,
+        client_ip: &client_ip,
+        before: Some(serde_json::json!({ "name": definition.name, "parts": before })),
+        after: Some(serde_json::json!({ "name": definition.name, "parts": definition.defaults() })),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Renders the saved template, or a draft of it, against sample data without sending anything.
+async fn preview_template(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(name): Path<String>,
+    request: Option<Json<PreviewRequest>>,
+) -> Result<Response, StatusCode> {
+    admin.require(Role::Owner)?;
+    let definition = find(&name)?;
+    let Json(request) = request.unwrap_or_default();
+
+    let saved = {
+        let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        email_templates::stored(&conn, definition.name).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    }.unwrap_or_else(|| definition.defaults());
+
+    let parts = Parts {
+        subject: request.subject.unwrap_or(saved.subject),
+        text: request.text.unwrap_or(saved.text),
+        html: request.html.unwrap_or(saved.html),
+    };
+
+    let mut data = definition.sample_data();
+    if let (Some(sample), Some(overrides)) = (data.as_object_mut(), request.data) {
+        sample.extend(overrides);
+    }
+
+    match email_templates::render_parts(definition.name, &parts, &data) {
+        Ok(rendered) => Ok(JsonResponse(rendered).into_response()),
+        Err(error) => Ok(template_error(error)),
+    }
+}
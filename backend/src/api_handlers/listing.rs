@@ -0,0 +1,75 @@
+use axum::http::StatusCode;
+use rusqlite::types::Value as SqlValue;
+
+/// The window a paginated admin list reads: `LIMIT per_page OFFSET offset`.
+#[derive(Debug, PartialEq)]
+pub struct Page {
+    pub page: i64,
+    pub per_page: i64,
+    pub offset: i64,
+}
+
+/// Resolves the `page` and `per_page` query parameters, defaulting to the first page and
+/// clamping `per_page` to `1..=max_per_page`. A page past what an i64 offset can address
+/// is a bad request, not a panic under the lock.
+pub fn page(page: Option<i64>, per_page: Option<i64>, default_per_page: i64, max_per_page: i64) -> Result<Page, StatusCode> {
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(default_per_page).clamp(1, max_per_page);
+    let offset = (page - 1).checked_mul(per_page).ok_or(StatusCode::BAD_REQUEST)?;
+    Ok(Page { page, per_page, offset })
+}
+
+/// Adds inclusive `YYYY-MM-DD` bounds on `created_at`, rejecting malformed dates with 400.
+pub fn created_between(
+    from: Option<&str>,
+    to: Option<&str>,
+    conditions: &mut Vec<&'static str>,
+    values: &mut Vec<SqlValue>,
+) -> Result<(), StatusCode> {
+    for date in [from, to].into_iter().flatten() {
+        if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    if let Some(from) = from {
+        conditions.push("date(created_at) >= date(?)");
+        values.push(SqlValue::Text(from.to_string()));
+    }
+    if let Some(to) = to {
+        conditions.push("date(created_at) <= date(?)");
+        values.push(SqlValue::Text(to.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paging_defaults_and_clamps() {
+        assert_eq!(page(None, None, 25, 100), Ok(Page { page: 1, per_page: 25, offset: 0 }));
+        assert_eq!(page(Some(0), Some(0), 25, 100), Ok(Page { page: 1, per_page: 1, offset: 0 }));
+        assert_eq!(page(Some(3), Some(500), 25, 100), Ok(Page { page: 3, per_page: 100, offset: 200 }));
+    }
+
+    #[test]
+    fn unaddressable_page_is_a_bad_request() {
+        assert_eq!(page(Some(i64::MAX), Some(100), 25, 100), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn malformed_dates_are_rejected_before_anything_is_added() {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        assert_eq!(
+            created_between(Some("2024-05-01"), Some("05/02/2024"), &mut conditions, &mut values),
+            Err(StatusCode::BAD_REQUEST)
+        );
+        assert!(conditions.is_empty() && values.is_empty());
+
+        assert_eq!(created_between(Some("2024-05-01"), None, &mut conditions, &mut values), Ok(()));
+        assert_eq!(conditions, ["date(created_at) >= date(?)"]);
+    }
+}
@@ -1,5 +1,15 @@
 pub mod contact;
+pub mod contacts;
+pub mod listing;
 pub mod projects;
 pub mod admin;
 pub mod knowledge;
 pub mod chat;
+pub mod users;
+pub mod two_factor;
+pub mod sessions;
+pub mod api_keys;
+pub mod audit;
+pub mod outbox;
+pub mod email_templates;
+pub mod webhooks;
\ No newline at end of file
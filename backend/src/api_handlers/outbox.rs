@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json as JsonResponse,
+    routing::{get, post},
+    Router,
+};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::audit::{self, AuditEvent};
+use crate::auth::{Actor, AdminUser, ClientIp, Role};
+use crate::db::AppState;
+use crate::outbox;
+use super::listing::{self, Page};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+#[derive(Serialize)]
+pub struct OutboxEmail {
+    id: i64,
+    kind: String,
+    recipient: String,
+    subject: String,
+    status: String,
+    attempts: i64,
+    next_attempt_at: i64,
+    last_error: Option<String>,
+    created_at: String,
+    sent_at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct OutboxResponse {
+    emails: Vec<OutboxEmail>,
+    total: i64,
+    page: i64,
+    per_page: i64,
+}
+
+/// `status` is 'pending', 'failed' or 'sent'; without it, everything not yet sent.
+#[derive(Deserialize)]
+pub struct OutboxQuery {
+    status: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(list_emails))
+        .route("/:id/retry", post(retry_email))
+        .with_state(state)
+}
+
+async fn list_emails(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Query(query): Query<OutboxQuery>,
+) -> Result<JsonResponse<OutboxResponse>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let (where_clause, mut values) = match query.status {
+        Some(status) if ["pending", "failed", "sent"].contains(&status.as_str()) => {
+            ("WHERE status = ?", vec![SqlValue::Text(status)])
+        }
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+        None => ("WHERE status != 'sent'", Vec::new()),
+    };
+
+    let Page { page, per_page, offset } = listing::page(query.page, query.per_page, DEFAULT_PER_PAGE, MAX_PER_PAGE)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM email_outbox {}", where_clause),
+        rusqlite::params_from_iter(values.iter()),
+        |row| row.get(0),
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, kind, recipient, subject, status, attempts, next_attempt_at, last_error, created_at, sent_at
+         FROM email_outbox {} ORDER BY id DESC LIMIT ? OFFSET ?",
+        where_clause
+    )).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    values.push(SqlValue::Integer(per_page));
+    values.push(SqlValue::Integer(offset));
+
+    let emails = stmt.query_map(rusqlite::params_from_iter(values.iter()), |row| {
+        Ok(OutboxEmail {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            recipient: row.get(2)?,
+            subject: row.get(3)?,
+            status: row.get(4)?,
+            attempts: row.get(5)?,
+            next_attempt_at: row.get(6)?,
+            last_error: row.get(7)?,
+            created_at: row.get(8)?,
+            sent_at: row.get(9)?,
+        })
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter_map(|e| e.ok())
+        .collect();
+
+    Ok(JsonResponse(OutboxResponse { emails, total, page, per_page }))
+}
+
+/// Requeues a pending or failed email for an immediate send with a fresh set of attempts.
+/// Sent email can't be retried (409).
+async fn retry_email(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let before: (String, i64) = conn.query_row(
+        "SELECT status, attempts FROM email_outbox WHERE id = ?1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if !outbox::retry(&conn, id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    audit::record(&conn, AuditEvent {
+        actor: &Actor::Admin { id: admin.id, username: admin.username.clone() },
+        action: "outbox.retry",
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: Some(serde_json::json!({ "status": before.0, "attempts": before.1 })),
+        after: Some(serde_json::json!({ "status": "pending", "attempts": 0 })),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -6,8 +6,12 @@ use axum::{
     Router,
 };
 use serde::{Deserialize, Serialize};
+use rusqlite::Connection;
 use std::sync::Arc;
+use crate::audit::{self, AuditEvent};
+use crate::auth::{ClientIp, Principal, Scope};
 use crate::db::AppState;
+use crate::webhooks;
 
 #[derive(Serialize, Deserialize)]
 pub struct Project {
@@ -35,6 +39,28 @@ pub fn router(state: Arc<AppState>) -> Router {
         .with_state(state)
 }
 
+fn fetch_project(conn: &Connection, id: i64) -> rusqlite::Result<Project> {
+    conn.query_row(
+        "SELECT id, title, description, technologies, github_url, demo_url, image_urls, featured, created_at, updated_at 
+         FROM projects WHERE id = ?1",
+        [id],
+        |row| {
+            Ok(Project {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                description: row.get(2)?,
+                technologies: row.get(3)?,
+                github_url: row.get(4)?,
+                demo_url: row.get(5)?,
+                image_urls: row.get(6)?,
+                featured: row.get::<_, i64>(7)? != 0,
+                created_at: row.get(8)?,
+                updated_at: row.get(9)?,
+            })
+        }
+    )
+}
+
 async fn list_projects(
     State(state): State<Arc<AppState>>,
 ) -> Result<JsonResponse<ProjectsResponse>, StatusCode> {
@@ -71,33 +97,19 @@ async fn get_project(
 ) -> Result<JsonResponse<Project>, StatusCode> {
     let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let project = conn.query_row(
-        "SELECT id, title, description, technologies, github_url, demo_url, image_urls, featured, created_at, updated_at 
-         FROM projects WHERE id = ?1",
-        [id],
-        |row| {
-            Ok(Project {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                description: row.get(2)?,
-                technologies: row.get(3)?,
-                github_url: row.get(4)?,
-                demo_url: row.get(5)?,
-                image_urls: row.get(6)?,
-                featured: row.get::<_, i64>(7)? != 0,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        }
-    ).map_err(|_| StatusCode::NOT_FOUND)?;
+    let project = fetch_project(&conn, id).map_err(|_| StatusCode::NOT_FOUND)?;
 
     Ok(JsonResponse(project))
 }
 
 async fn create_project(
     State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
     Json(project): Json<Project>,
 ) -> Result<JsonResponse<Project>, StatusCode> {
+    caller.require(Scope::ProjectsWrite)?;
+
     let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     conn.execute(
@@ -115,20 +127,36 @@ async fn create_project(
     ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let id = conn.last_insert_rowid();
+    let project = fetch_project(&conn, id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&conn, AuditEvent {
+        actor: &caller.actor,
+        action: "project.create",
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: None,
+        after: audit::snapshot(&project),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    webhooks::enqueue(&conn, "project.created", &serde_json::json!({ "project": project }))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(JsonResponse(Project {
-        id: Some(id),
-        ..project
-    }))
+    Ok(JsonResponse(project))
 }
 
 async fn update_project(
     State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
     Path(id): Path<i64>,
     Json(project): Json<Project>,
 ) -> Result<JsonResponse<Project>, StatusCode> {
+    caller.require(Scope::ProjectsWrite)?;
+
     let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let before = fetch_project(&conn, id).map_err(|_| StatusCode::NOT_FOUND)?;
+
     conn.execute(
         "UPDATE projects SET 
          title = ?1, description = ?2, technologies = ?3, github_url = ?4, 
@@ -146,19 +174,53 @@ async fn update_project(
         ],
     ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let project = fetch_project(&conn, id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&conn, AuditEvent {
+        actor: &caller.actor,
+        action: "project.update",
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: audit::snapshot(&before),
+        after: audit::snapshot(&project),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    webhooks::enqueue(&conn, "project.updated", &serde_json::json!({ "project": project }))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     Ok(JsonResponse(project))
 }
 
 async fn delete_project(
     State(state): State<Arc<AppState>>,
+    caller: Principal,
+    ClientIp(client_ip): ClientIp,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, StatusCode> {
+    caller.require(Scope::ProjectsWrite)?;
+
     let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let before = fetch_project(&conn, id).map_err(|_| StatusCode::NOT_FOUND)?;
+
     conn.execute(
         "DELETE FROM projects WHERE id = ?1",
         [id],
     ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    tracing::info!("Project {} deleted by {}", id, caller.actor);
+
+    audit::record(&conn, AuditEvent {
+        actor: &caller.actor,
+        action: "project.delete",
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: audit::snapshot(&before),
+        after: None,
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    webhooks::enqueue(&conn, "project.deleted", &serde_json::json!({ "project": before }))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,87 @@
+use axum::{
+    extract::{State, Path},
+    http::StatusCode,
+    response::Json as JsonResponse,
+    routing::{delete, get},
+    Router,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use crate::auth::AdminIdentity;
+use crate::db::AppState;
+use crate::sessions;
+
+#[derive(Serialize)]
+pub struct ActiveSession {
+    id: String,
+    user_agent: String,
+    ip: String,
+    created_at: String,
+    last_seen_at: String,
+    current: bool,
+}
+
+#[derive(Serialize)]
+pub struct SessionsResponse {
+    sessions: Vec<ActiveSession>,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(list_sessions))
+        .route("/:id", delete(revoke_session))
+        .with_state(state)
+}
+
+async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    admin: AdminIdentity,
+) -> Result<JsonResponse<SessionsResponse>, StatusCode> {
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, user_agent, ip, created_at, last_seen_at
+         FROM admin_sessions
+         WHERE user_id = ?1 AND revoked_at IS NULL AND expires_at > ?2
+         ORDER BY last_seen_at DESC"
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let now = chrono::Utc::now().timestamp();
+    let session_list = stmt.query_map(rusqlite::params![admin.id, now], |row| {
+        let id: String = row.get(0)?;
+        Ok(ActiveSession {
+            current: id == admin.session_id,
+            id,
+            user_agent: row.get(1)?,
+            ip: row.get(2)?,
+            created_at: row.get(3)?,
+            last_seen_at: row.get(4)?,
+        })
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter_map(|s| s.ok())
+        .collect();
+
+    Ok(JsonResponse(SessionsResponse { sessions: session_list }))
+}
+
+async fn revoke_session(
+    State(state): State<Arc<AppState>>,
+    admin: AdminIdentity,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Admins can only end their own sessions
+    let owner: i64 = conn.query_row(
+        "SELECT user_id FROM admin_sessions WHERE id = ?1",
+        [&id],
+        |row| row.get(0),
+    ).map_err(|_| StatusCode::NOT_FOUND)?;
+    if owner != admin.id {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    sessions::revoke(&conn, &id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
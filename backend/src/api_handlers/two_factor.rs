@@ -0,0 +1,265 @@
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::Json as JsonResponse,
+    routing::post,
+    Router,
+};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::auth::AdminUser;
+use crate::db::AppState;
+use crate::totp;
+
+#[derive(Serialize)]
+pub struct SetupResponse {
+    secret: String,
+    provisioning_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmRequest {
+    code: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    recovery_codes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DisableRequest {
+    password: String,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/setup", post(setup))
+        .route("/confirm", post(confirm))
+        .route("/disable", post(disable))
+        .with_state(state)
+}
+
+/// Replaces any existing recovery codes for the user and returns the new plaintext codes.
+fn replace_recovery_codes(conn: &Connection, user_id: i64) -> rusqlite::Result<Vec<String>> {
+    conn.execute("DELETE FROM admin_recovery_codes WHERE user_id = ?1", [user_id])?;
+
+    let codes = totp::generate_recovery_codes();
+    for code in &codes {
+        conn.execute(
+            "INSERT INTO admin_recovery_codes (user_id, code_hash) VALUES (?1, ?2)",
+            params![user_id, totp::hash_recovery_code(code)],
+        )?;
+    }
+
+    Ok(codes)
+}
+
+/// Checks the second login factor: either a current TOTP code that hasn't been
+/// used before, or an unused recovery code, which is consumed.
+pub fn verify_code(conn: &Connection, user_id: i64, code: &str) -> rusqlite::Result<bool> {
+    let code = code.trim();
+
+    if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        let (secret, last_step): (Option<String>, Option<i64>) = conn.query_row(
+            "SELECT totp_secret, totp_last_step FROM admin_users WHERE id = ?1 AND totp_enabled = 1",
+            [user_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        let step = secret.and_then(|secret| totp::matching_step(&secret, code));
+        return match step {
+            Some(step) if last_step.is_none_or(|last| step as i64 > last) => {
+                conn.execute(
+                    "UPDATE admin_users SET totp_last_step = ?1 WHERE id = ?2",
+                    params![step as i64, user_id],
+                )?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        };
+    }
+
+    let used = conn.execute(
+        "UPDATE admin_recovery_codes SET used_at = CURRENT_TIMESTAMP
+         WHERE id = (SELECT id FROM admin_recovery_codes
+                     WHERE user_id = ?1 AND code_hash = ?2 AND used_at IS NULL LIMIT 1)",
+        params![user_id, totp::hash_recovery_code(code)],
+    )?;
+
+    Ok(used > 0)
+}
+
+/// Starts enrollment by storing a fresh secret. 2FA stays off until it is confirmed.
+async fn setup(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+) -> Result<JsonResponse<SetupResponse>, StatusCode> {
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let enabled: bool = conn.query_row(
+        "SELECT totp_enabled FROM admin_users WHERE id = ?1",
+        [admin.id],
+        |row| Ok(row.get::<_, i64>(0)? != 0),
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if enabled {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let secret = totp::new_secret();
+    let provisioning_uri = totp::provisioning_uri(&secret, &admin.username)
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    conn.execute(
+        "UPDATE admin_users SET totp_secret = ?1, totp_last_step = NULL WHERE id = ?2",
+        params![secret, admin.id],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(SetupResponse { secret, provisioning_uri }))
+}
+
+/// Turns 2FA on once the admin proves their authenticator produces valid codes.
+async fn confirm(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Json(request): Json<ConfirmRequest>,
+) -> Result<JsonResponse<RecoveryCodesResponse>, StatusCode> {
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (secret, enabled): (Option<String>, bool) = conn.query_row(
+        "SELECT totp_secret, totp_enabled FROM admin_users WHERE id = ?1",
+        [admin.id],
+        |row| Ok((row.get(0)?, row.get::<_, i64>(1)? != 0)),
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if enabled {
+        return Err(StatusCode::CONFLICT);
+    }
+    let secret = secret.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let step = totp::matching_step(&secret, request.code.trim()).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    conn.execute(
+        "UPDATE admin_users SET totp_enabled = 1, totp_last_step = ?1 WHERE id = ?2",
+        params![step as i64, admin.id],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let recovery_codes = replace_recovery_codes(&conn, admin.id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(RecoveryCodesResponse { recovery_codes }))
+}
+
+async fn disable(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Json(request): Json<DisableRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let stored_hash: String = {
+        let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.query_row(
+            "SELECT password_hash FROM admin_users WHERE id = ?1",
+            [admin.id],
+            |row| row.get(0),
+        ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+
+    // Off the database lock, like the password check at login
+    let valid = tokio::task::spawn_blocking(move || bcrypt::verify(&request.password, &stored_hash))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !valid {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    conn.execute(
+        "UPDATE admin_users SET totp_secret = NULL, totp_enabled = 0, totp_last_step = NULL WHERE id = ?1",
+        [admin.id],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    conn.execute(
+        "DELETE FROM admin_recovery_codes WHERE user_id = ?1",
+        [admin.id],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use totp_rs::{Algorithm, Secret, TOTP};
+
+    const STEP_SECS: i64 = 30;
+
+    /// An in-memory database with one admin who has 2FA on, and their secret.
+    fn enrolled() -> (Connection, i64, String) {
+        let conn = AppState::migrate(Connection::open_in_memory().unwrap()).unwrap();
+
+        let secret = totp::new_secret();
+        conn.execute(
+            "INSERT INTO admin_users (username, password_hash, totp_secret, totp_enabled) VALUES ('jane', '', ?1, 1)",
+            [&secret],
+        ).unwrap();
+
+        let user_id = conn.last_insert_rowid();
+        (conn, user_id, secret)
+    }
+
+    fn code(secret: &str, steps_from_now: i64) -> String {
+        let bytes = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+        let totp = TOTP::new(Algorithm::SHA1, 6, 1, STEP_SECS as u64, bytes, None, String::new()).unwrap();
+        totp.generate((chrono::Utc::now().timestamp() + steps_from_now * STEP_SECS) as u64)
+    }
+
+    #[test]
+    fn current_code_works_once() {
+        let (conn, user_id, secret) = enrolled();
+        let current = code(&secret, 0);
+
+        assert!(verify_code(&conn, user_id, &current).unwrap());
+        assert!(!verify_code(&conn, user_id, &current).unwrap(), "a used code must not be accepted again");
+    }
+
+    #[test]
+    fn older_code_is_rejected_after_a_newer_one() {
+        let (conn, user_id, secret) = enrolled();
+
+        assert!(verify_code(&conn, user_id, &code(&secret, 1)).unwrap());
+        assert!(!verify_code(&conn, user_id, &code(&secret, 0)).unwrap());
+        assert!(!verify_code(&conn, user_id, &code(&secret, -1)).unwrap());
+    }
+
+    #[test]
+    fn codes_outside_the_skew_are_rejected() {
+        let (conn, user_id, secret) = enrolled();
+
+        assert!(!verify_code(&conn, user_id, &code(&secret, -3)).unwrap());
+        assert!(!verify_code(&conn, user_id, &code(&secret, 3)).unwrap());
+        assert!(!verify_code(&conn, user_id, &code(&totp::new_secret(), 0)).unwrap());
+    }
+
+    #[test]
+    fn recovery_code_works_once() {
+        let (conn, user_id, _) = enrolled();
+        let codes = replace_recovery_codes(&conn, user_id).unwrap();
+
+        // Typed loosely: upper case, no dash, surrounding spaces
+        let typed = format!("  {}  ", codes[0].replace('-', "").to_uppercase());
+        assert!(verify_code(&conn, user_id, &typed).unwrap());
+        assert!(!verify_code(&conn, user_id, &codes[0]).unwrap());
+        assert!(verify_code(&conn, user_id, &codes[1]).unwrap());
+    }
+
+    #[test]
+    fn replaced_recovery_codes_stop_working() {
+        let (conn, user_id, _) = enrolled();
+        let old = replace_recovery_codes(&conn, user_id).unwrap();
+        replace_recovery_codes(&conn, user_id).unwrap();
+
+        assert!(!verify_code(&conn, user_id, &old[0]).unwrap());
+    }
+}
@@ -0,0 +1,214 @@
+use axum::{
+    extract::{Json, State, Path},
+    http::StatusCode,
+    response::Json as JsonResponse,
+    routing::{get, put},
+    Router,
+};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::auth::{AdminUser, Role};
+use crate::db::AppState;
+use crate::password_policy;
+
+#[derive(Serialize)]
+pub struct AdminAccount {
+    id: i64,
+    username: String,
+    email: Option<String>,
+    role: Role,
+    disabled: bool,
+    must_change_password: bool,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+pub struct AdminAccountsResponse {
+    users: Vec<AdminAccount>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    username: String,
+    password: String,
+    role: Role,
+    email: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateUserRequest {
+    role: Option<Role>,
+    disabled: Option<bool>,
+    // An empty string removes the address
+    email: Option<String>,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(list_users).post(create_user))
+        .route("/:id", put(update_user).delete(delete_user))
+        .with_state(state)
+}
+
+fn account_from_row(row: &rusqlite::Row) -> rusqlite::Result<AdminAccount> {
+    Ok(AdminAccount {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        email: row.get(2)?,
+        role: Role::parse(&row.get::<_, String>(3)?).unwrap_or(Role::Viewer),
+        disabled: row.get::<_, i64>(4)? != 0,
+        must_change_password: row.get::<_, i64>(5)? != 0,
+        created_at: row.get(6)?,
+    })
+}
+
+/// Trims an email address, treating an empty one as none. Only the `@` is checked;
+/// a bad address just means reset emails never arrive.
+fn normalize_email(email: Option<&str>) -> Result<Option<String>, StatusCode> {
+    match email.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(email) if email.contains('@') => Ok(Some(email.to_string())),
+        Some(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+fn get_account(conn: &Connection, id: i64) -> rusqlite::Result<AdminAccount> {
+    conn.query_row(
+        "SELECT id, username, email, role, disabled, must_change_password, created_at
+         FROM admin_users WHERE id = ?1",
+        [id],
+        account_from_row,
+    )
+}
+
+/// Counts enabled owners other than `excluding`, so the last one can't be removed.
+fn other_active_owners(conn: &Connection, excluding: i64) -> Result<i64, StatusCode> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM admin_users WHERE role = 'owner' AND disabled = 0 AND id != ?1",
+        [excluding],
+        |row| row.get(0),
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn list_users(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+) -> Result<JsonResponse<AdminAccountsResponse>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, username, email, role, disabled, must_change_password, created_at
+         FROM admin_users ORDER BY created_at ASC"
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let users = stmt.query_map([], account_from_row)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter_map(|u| u.ok())
+        .collect();
+
+    Ok(JsonResponse(AdminAccountsResponse { users }))
+}
+
+async fn create_user(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Json(request): Json<CreateUserRequest>,
+) -> Result<JsonResponse<AdminAccount>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let username = request.username.trim();
+    if username.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    password_policy::validate(&request.password)?;
+    let email = normalize_email(request.email.as_deref())?;
+
+    // Hashed on the blocking pool, before taking the lock
+    let password = request.password;
+    let password_hash = tokio::task::spawn_blocking(move || password_policy::hash(&password))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let taken = conn.query_row(
+        "SELECT 1 FROM admin_users WHERE username = ?1",
+        [username],
+        |_| Ok(()),
+    ).optional().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if taken.is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    // The owner hands out a temporary password, which the new admin has to replace
+    conn.execute(
+        "INSERT INTO admin_users (username, email, password_hash, role, must_change_password) VALUES (?1, ?2, ?3, ?4, 1)",
+        params![username, email, password_hash, request.role.as_str()],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let account = get_account(&conn, conn.last_insert_rowid())
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(account))
+}
+
+async fn update_user(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdateUserRequest>,
+) -> Result<JsonResponse<AdminAccount>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let account = get_account(&conn, id).map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let role = request.role.unwrap_or(account.role);
+    let disabled = request.disabled.unwrap_or(account.disabled);
+    let email = match request.email {
+        Some(email) => normalize_email(Some(&email))?,
+        None => account.email,
+    };
+
+    let stays_active_owner = role == Role::Owner && !disabled;
+    if account.role == Role::Owner && !stays_active_owner && other_active_owners(&conn, id)? == 0 {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    conn.execute(
+        "UPDATE admin_users SET role = ?1, disabled = ?2, email = ?3 WHERE id = ?4",
+        params![role.as_str(), disabled as i64, email, id],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let account = get_account(&conn, id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(account))
+}
+
+async fn delete_user(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let account = get_account(&conn, id).map_err(|_| StatusCode::NOT_FOUND)?;
+
+    if account.role == Role::Owner && other_active_owners(&conn, id)? == 0 {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    conn.execute(
+        "DELETE FROM admin_users WHERE id = ?1",
+        [id],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
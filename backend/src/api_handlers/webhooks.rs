@@ -0,0 +1,501 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+    response::Json as JsonResponse,
+    routing::{get, post},
+    Router,
+};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::audit::{self, AuditEvent};
+use crate::auth::{Actor, AdminUser, ClientIp, Role};
+use crate::db::AppState;
+use crate::webhooks;
+use super::listing::{self, Page};
+
+const MAX_DESCRIPTION_LEN: usize = 200;
+const MAX_URL_LEN: usize = 2000;
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+/// A subscription as listed. The secret is only shown when it's created or rotated.
+#[derive(Serialize)]
+pub struct Webhook {
+    id: i64,
+    url: String,
+    description: String,
+    events: Vec<String>,
+    format: String,
+    active: bool,
+    created_by: String,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(Serialize)]
+pub struct WebhooksResponse {
+    webhooks: Vec<Webhook>,
+    events: &'static [&'static str],
+}
+
+#[derive(Serialize)]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    // Shown once; rotate it if it's lost
+    secret: String,
+}
+
+#[derive(Serialize)]
+pub struct SecretResponse {
+    secret: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateWebhookRequest {
+    url: String,
+    #[serde(default)]
+    description: String,
+    events: Vec<String>,
+    format: Option<String>,
+    active: Option<bool>,
+}
+
+/// Fields left out keep their current value.
+#[derive(Deserialize)]
+pub struct UpdateWebhookRequest {
+    url: Option<String>,
+    description: Option<String>,
+    events: Option<Vec<String>>,
+    format: Option<String>,
+    active: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct Delivery {
+    id: i64,
+    webhook_id: i64,
+    event: String,
+    payload: String,
+    status: String,
+    attempts: i64,
+    next_attempt_at: i64,
+    response_status: Option<u16>,
+    last_error: Option<String>,
+    created_at: String,
+    delivered_at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DeliveriesResponse {
+    deliveries: Vec<Delivery>,
+    total: i64,
+    page: i64,
+    per_page: i64,
+}
+
+/// `status` is 'pending', 'delivered' or 'failed'; without it, every delivery.
+#[derive(Deserialize)]
+pub struct DeliveriesQuery {
+    status: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(list_webhooks).post(create_webhook))
+        .route("/:id", get(get_webhook).put(update_webhook).delete(delete_webhook))
+        .route("/:id/secret", post(rotate_secret))
+        .route("/:id/test", post(test_webhook))
+        .route("/:id/deliveries", get(list_deliveries))
+        .route("/:id/deliveries/:delivery_id/retry", post(retry_delivery))
+        .with_state(state)
+}
+
+const WEBHOOK_COLUMNS: &str = "id, url, description, events, format, active, created_by, created_at, updated_at";
+
+fn webhook_from_row(row: &Row) -> rusqlite::Result<Webhook> {
+    Ok(Webhook {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        description: row.get(2)?,
+        events: webhooks::events_from_string(&row.get::<_, String>(3)?),
+        format: row.get(4)?,
+        active: row.get::<_, i64>(5)? != 0,
+        created_by: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+fn fetch_webhook(conn: &Connection, id: i64) -> Result<Webhook, StatusCode> {
+    conn.query_row(
+        &format!("SELECT {} FROM webhooks WHERE id = ?1", WEBHOOK_COLUMNS),
+        [id],
+        webhook_from_row,
+    ).optional()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Only http(s) URLs whose host resolves to public addresses are accepted; 400 otherwise.
+async fn valid_url(url: &str) -> Result<(), StatusCode> {
+    let well_formed = url.len() <= MAX_URL_LEN
+        && reqwest::Url::parse(url)
+            .is_ok_and(|parsed| ["http", "https"].contains(&parsed.scheme()) && parsed.host_str().is_some());
+    if !well_formed {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    webhooks::resolve_target(url).await.map(|_| ()).map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+/// Known event names, without duplicates; 400 for an empty list or an unknown name.
+fn valid_events(events: &[String]) -> Result<Vec<String>, StatusCode> {
+    if events.is_empty() || events.iter().any(|event| !webhooks::EVENTS.contains(&event.as_str())) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut events = events.to_vec();
+    events.sort();
+    events.dedup();
+    Ok(events)
+}
+
+fn valid_format(format: &str) -> Result<(), StatusCode> {
+    if webhooks::FORMATS.contains(&format) {
+        Ok(())
+    } else {
+        Err(StatusCode::BAD_REQUEST)
+    }
+}
+
+fn valid_description(description: &str) -> Result<String, StatusCode> {
+    let description = description.trim();
+    if description.chars().count() > MAX_DESCRIPTION_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(description.to_string())
+}
+
+fn admin_actor(admin: &AdminUser) -> Actor {
+    Actor::Admin { id: admin.id, username: admin.username.clone() }
+}
+
+async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+) -> Result<JsonResponse<WebhooksResponse>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM webhooks ORDER BY id", WEBHOOK_COLUMNS))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let webhook_list = stmt.query_map([], webhook_from_row)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter_map(|w| w.ok())
+        .collect();
+
+    Ok(JsonResponse(WebhooksResponse { webhooks: webhook_list, events: webhooks::EVENTS }))
+}
+
+async fn get_webhook(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Webhook>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    fetch_webhook(&conn, id).map(JsonResponse)
+}
+
+async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    ClientIp(client_ip): ClientIp,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<JsonResponse<CreatedWebhook>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let url = request.url.trim();
+    valid_url(url).await?;
+    let events = valid_events(&request.events)?;
+    let format = request.format.unwrap_or_else(|| "json".to_string());
+    valid_format(&format)?;
+    let description = valid_description(&request.description)?;
+
+    let secret = webhooks::new_secret();
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    conn.execute(
+        "INSERT INTO webhooks (url, description, events, format, secret, active, created_by)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            url, description, webhooks::events_to_string(&events), format, secret,
+            request.active.unwrap_or(true), admin.username,
+        ],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let id = conn.last_insert_rowid();
+    let webhook = fetch_webhook(&conn, id)?;
+
+    audit::record(&conn, AuditEvent {
+        actor: &admin_actor(&admin),
+        action: "webhook.create",
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: None,
+        after: audit::snapshot(&webhook),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(CreatedWebhook { webhook, secret }))
+}
+
+async fn update_webhook(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdateWebhookRequest>,
+) -> Result<JsonResponse<Webhook>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let url = request.url.as_deref().map(str::trim);
+    if let Some(url) = url {
+        valid_url(url).await?;
+    }
+    let events = request.events.as_deref().map(valid_events).transpose()?;
+    if let Some(format) = &request.format {
+        valid_format(format)?;
+    }
+    let description = request.description.as_deref().map(valid_description).transpose()?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let before = fetch_webhook(&conn, id)?;
+
+    conn.execute(
+        "UPDATE webhooks SET url = ?1, description = ?2, events = ?3, format = ?4, active = ?5,
+         updated_at = CURRENT_TIMESTAMP WHERE id = ?6",
+        rusqlite::params![
+            url.unwrap_or(&before.url),
+            description.as_ref().unwrap_or(&before.description),
+            webhooks::events_to_string(events.as_ref().unwrap_or(&before.events)),
+            request.format.as_ref().unwrap_or(&before.format),
+            request.active.unwrap_or(before.active),
+            id,
+        ],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let webhook = fetch_webhook(&conn, id)?;
+
+    audit::record(&conn, AuditEvent {
+        actor: &admin_actor(&admin),
+        action: "webhook.update",
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: audit::snapshot(&before),
+        after: audit::snapshot(&webhook),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(webhook))
+}
+
+/// Deletes a webhook along with its delivery log.
+async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let before = fetch_webhook(&conn, id)?;
+
+    conn.execute("DELETE FROM webhooks WHERE id = ?1", [id])
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&conn, AuditEvent {
+        actor: &admin_actor(&admin),
+        action: "webhook.delete",
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: audit::snapshot(&before),
+        after: None,
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Replaces the signing secret. Deliveries still queued are signed with the new one.
+async fn rotate_secret(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    ClientIp(client_ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<SecretResponse>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let secret = webhooks::new_secret();
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let updated = conn.execute(
+        "UPDATE webhooks SET secret = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+        rusqlite::params![secret, id],
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if updated == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    audit::record(&conn, AuditEvent {
+        actor: &admin_actor(&admin),
+        action: "webhook.rotate_secret",
+        target_id: Some(id),
+        client_ip: &client_ip,
+        before: None,
+        after: None,
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(JsonResponse(SecretResponse { secret }))
+}
+
+/// Queues a `ping` delivery to this webhook, whatever it subscribes to. The result shows
+/// up in its delivery log.
+async fn test_webhook(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, JsonResponse<Delivery>), StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let webhook = fetch_webhook(&conn, id)?;
+
+    let delivery_id = webhooks::enqueue_for(
+        &conn, id, &webhook.format, webhooks::PING_EVENT,
+        &serde_json::json!({ "webhook_id": id, "sent_by": admin.username }),
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let delivery = conn.query_row(
+        &format!("SELECT {} FROM webhook_deliveries WHERE id = ?1", DELIVERY_COLUMNS),
+        [delivery_id],
+        delivery_from_row,
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok((StatusCode::ACCEPTED, JsonResponse(delivery)))
+}
+
+const DELIVERY_COLUMNS: &str = "id, webhook_id, event, payload, status, attempts, next_attempt_at,
+    response_status, last_error, created_at, delivered_at";
+
+fn delivery_from_row(row: &Row) -> rusqlite::Result<Delivery> {
+    Ok(Delivery {
+        id: row.get(0)?,
+        webhook_id: row.get(1)?,
+        event: row.get(2)?,
+        payload: row.get(3)?,
+        status: row.get(4)?,
+        attempts: row.get(5)?,
+        next_attempt_at: row.get(6)?,
+        response_status: row.get(7)?,
+        last_error: row.get(8)?,
+        created_at: row.get(9)?,
+        delivered_at: row.get(10)?,
+    })
+}
+
+/// The delivery log for one webhook, newest first.
+async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(id): Path<i64>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<JsonResponse<DeliveriesResponse>, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let mut where_clause = "WHERE webhook_id = ?".to_string();
+    let mut values = vec![SqlValue::Integer(id)];
+    match query.status {
+        Some(status) if ["pending", "delivered", "failed"].contains(&status.as_str()) => {
+            where_clause.push_str(" AND status = ?");
+            values.push(SqlValue::Text(status));
+        }
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+        None => {}
+    }
+
+    let Page { page, per_page, offset } = listing::page(query.page, query.per_page, DEFAULT_PER_PAGE, MAX_PER_PAGE)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    fetch_webhook(&conn, id)?;
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM webhook_deliveries {}", where_clause),
+        rusqlite::params_from_iter(values.iter()),
+        |row| row.get(0),
+    ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM webhook_deliveries {} ORDER BY id DESC LIMIT ? OFFSET ?",
+        DELIVERY_COLUMNS, where_clause
+    )).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    values.push(SqlValue::Integer(per_page));
+    values.push(SqlValue::Integer(offset));
+
+    let deliveries = stmt.query_map(rusqlite::params_from_iter(values.iter()), delivery_from_row)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter_map(|d| d.ok())
+        .collect();
+
+    Ok(JsonResponse(DeliveriesResponse { deliveries, total, page, per_page }))
+}
+
+/// Requeues a pending or failed delivery for an immediate attempt with a fresh set of
+/// attempts. Delivered ones can't be retried (409).
+async fn retry_delivery(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    ClientIp(client_ip): ClientIp,
+    Path((id, delivery_id)): Path<(i64, i64)>,
+) -> Result<StatusCode, StatusCode> {
+    admin.require(Role::Owner)?;
+
+    let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let before: (String, i64) = conn.query_row(
+        "SELECT status, attempts FROM webhook_deliveries WHERE id = ?1 AND webhook_id = ?2",
+        [delivery_id, id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if !webhooks::retry(&conn, delivery_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    audit::record(&conn, AuditEvent {
+        actor: &admin_actor(&admin),
+        action: "webhook.retry",
+        target_id: Some(delivery_id),
+        client_ip: &client_ip,
+        before: Some(serde_json::json!({ "webhook_id": id, "status": before.0, "attempts": before.1 })),
+        after: Some(serde_json::json!({ "webhook_id": id, "status": "pending", "attempts": 0 })),
+    }).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,142 @@
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use crate::auth::Scope;
+use crate::tokens;
+
+// Every key starts with this so the auth layer can tell keys from session tokens
+pub const KEY_PREFIX: &str = "pk_";
+
+pub struct NewKey {
+    pub id: i64,
+    pub key: String,
+}
+
+pub struct VerifiedKey {
+    pub id: i64,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+pub fn scopes_to_string(scopes: &[Scope]) -> String {
+    scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+pub fn scopes_from_string(scopes: &str) -> Vec<Scope> {
+    scopes.split_whitespace().filter_map(Scope::parse).collect()
+}
+
+/// Creates a key of the form `pk_<lookup id>_<secret>`. The plaintext is only
+/// returned here; the database keeps the lookup id and a SHA-256 of the whole key.
+pub fn create(conn: &Connection, name: &str, scopes: &[Scope], created_by: i64) -> Result<NewKey> {
+    // Hex so the lookup id never contains the '_' separator
+    let lookup_id = format!("{:012x}", rand::thread_rng().next_u64() >> 16);
+    let key = format!("{}{}_{}", KEY_PREFIX, lookup_id, tokens::random(32));
+
+    conn.execute(
+        "INSERT INTO api_keys (name, lookup_id, key_hash, scopes, created_by) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![name, lookup_id, tokens::hash(&key), scopes_to_string(scopes), created_by],
+    )?;
+
+    Ok(NewKey { id: conn.last_insert_rowid(), key })
+}
+
+/// Looks up an unrevoked key and records that it was used.
+pub fn verify(conn: &Connection, key: &str) -> Result<Option<VerifiedKey>> {
+    let Some((lookup_id, _)) = key.strip_prefix(KEY_PREFIX).and_then(|rest| rest.split_once('_')) else {
+        return Ok(None);
+    };
+
+    let stored: Option<(i64, String, String, String)> = conn.query_row(
+        "SELECT id, name, key_hash, scopes FROM api_keys WHERE lookup_id = ?1 AND revoked_at IS NULL",
+        [lookup_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    ).optional()?;
+
+    let Some((id, name, key_hash, scopes)) = stored else {
+        return Ok(None);
+    };
+    if key_hash != tokens::hash(key) {
+        return Ok(None);
+    }
+
+    conn.execute(
+        "UPDATE api_keys SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        [id],
+    )?;
+
+    Ok(Some(VerifiedKey { id, name, scopes: scopes_from_string(&scopes) }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::AppState;
+
+    fn db() -> Connection {
+        let conn = AppState::migrate(Connection::open_in_memory().unwrap()).unwrap();
+        conn.execute("INSERT INTO admin_users (username, password_hash) VALUES ('jane', 'x')", []).unwrap();
+        conn
+    }
+
+    #[test]
+    fn created_key_verifies_with_its_scopes() {
+        let conn = db();
+        let new = create(&conn, "ci", &[Scope::ContactsRead, Scope::ProjectsWrite], 1).unwrap();
+
+        let key = verify(&conn, &new.key).unwrap().unwrap();
+        assert_eq!(key.id, new.id);
+        assert_eq!(key.name, "ci");
+        assert!(key.scopes == vec![Scope::ContactsRead, Scope::ProjectsWrite]);
+    }
+
+    #[test]
+    fn only_a_hash_of_the_key_is_stored() {
+        let conn = db();
+        let new = create(&conn, "ci", &[Scope::ContactsRead], 1).unwrap();
+
+        let stored: String = conn.query_row("SELECT key_hash FROM api_keys", [], |row| row.get(0)).unwrap();
+        assert_eq!(stored, tokens::hash(&new.key));
+        assert!(!stored.contains(&new.key));
+    }
+
+    #[test]
+    fn wrong_secret_or_malformed_keys_are_rejected() {
+        let conn = db();
+        let new = create(&conn, "ci", &[Scope::ContactsRead], 1).unwrap();
+        let (prefix, _) = new.key.rsplit_once('_').unwrap();
+
+        assert!(verify(&conn, &format!("{}_{}", prefix, tokens::random(32))).unwrap().is_none());
+        assert!(verify(&conn, &new.key[KEY_PREFIX.len()..]).unwrap().is_none());
+        assert!(verify(&conn, "pk_nounderscore").unwrap().is_none());
+        assert!(verify(&conn, "").unwrap().is_none());
+    }
+
+    #[test]
+    fn revoked_keys_are_rejected() {
+        let conn = db();
+        let new = create(&conn, "ci", &[Scope::ContactsRead], 1).unwrap();
+        conn.execute("UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP WHERE id = ?1", [new.id]).unwrap();
+
+        assert!(verify(&conn, &new.key).unwrap().is_none());
+    }
+
+    #[test]
+    fn verifying_records_last_use() {
+        let conn = db();
+        let new = create(&conn, "ci", &[Scope::ContactsRead], 1).unwrap();
+        let last_used = |conn: &Connection| -> Option<String> {
+            conn.query_row("SELECT last_used_at FROM api_keys", [], |row| row.get(0)).unwrap()
+        };
+
+        assert!(last_used(&conn).is_none());
+        verify(&conn, &new.key).unwrap().unwrap();
+        assert!(last_used(&conn).is_some());
+    }
+
+    #[test]
+    fn unknown_scopes_are_ignored() {
+        let scopes = scopes_from_string("contacts:read admin:everything  projects:write");
+        assert!(scopes == vec![Scope::ContactsRead, Scope::ProjectsWrite]);
+        assert_eq!(scopes_to_string(&scopes), "contacts:read projects:write");
+    }
+}
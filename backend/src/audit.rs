@@ -0,0 +1,70 @@
+use rusqlite::{params, Connection, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::auth::Actor;
+
+/// One row of the audit log. `before` and `after` are snapshots of the target;
+/// only the fields that differ between them are stored.
+pub struct AuditEvent<'a> {
+    pub actor: &'a Actor,
+    pub action: &'a str,
+    pub target_id: Option<i64>,
+    pub client_ip: &'a str,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Serializes a snapshot for [`AuditEvent`]; serialization of our own row types can't fail.
+pub fn snapshot<T: Serialize>(value: &T) -> Option<Value> {
+    serde_json::to_value(value).ok()
+}
+
+/// Builds `{"field": {"before": .., "after": ..}}` for every top-level field that changed.
+/// A missing side (creation or deletion) is recorded as null.
+pub fn diff(before: Option<&Value>, after: Option<&Value>) -> Value {
+    let empty = Map::new();
+    let before = before.and_then(Value::as_object).unwrap_or(&empty);
+    let after = after.and_then(Value::as_object).unwrap_or(&empty);
+
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        let old = before.get(key).unwrap_or(&Value::Null);
+        let new = after.get(key).unwrap_or(&Value::Null);
+        if old != new && !changes.contains_key(key) {
+            changes.insert(key.clone(), serde_json::json!({ "before": old, "after": new }));
+        }
+    }
+
+    Value::Object(changes)
+}
+
+pub fn record(conn: &Connection, event: AuditEvent) -> Result<()> {
+    let changes = diff(event.before.as_ref(), event.after.as_ref());
+
+    conn.execute(
+        "INSERT INTO audit_log (actor_kind, actor_id, actor_name, action, target_id, client_ip, changes)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            event.actor.kind(),
+            event.actor.id(),
+            event.actor.name(),
+            event.action,
+            event.target_id,
+            event.client_ip,
+            changes.to_string(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Records a failed login, where there is no authenticated actor, only the username tried.
+pub fn record_failed_login(conn: &Connection, username: &str, client_ip: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (actor_kind, actor_id, actor_name, action, target_id, client_ip, changes)
+         VALUES ('anonymous', NULL, ?1, 'login.failed', NULL, ?2, '{}')",
+        params![username, client_ip],
+    )?;
+
+    Ok(())
+}
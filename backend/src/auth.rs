@@ -0,0 +1,513 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, Method, StatusCode},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{net::SocketAddr, sync::Arc};
+use crate::api_keys;
+use crate::cookies;
+use crate::db::AppState;
+use crate::sessions;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Access tokens last 15 minutes unless ADMIN_TOKEN_TTL_SECS says otherwise;
+// clients renew them with the session's refresh token
+const DEFAULT_TOKEN_TTL_SECS: i64 = 15 * 60;
+// Time allowed between the password step and the two-factor step of a login
+const TWO_FACTOR_CHALLENGE_TTL_SECS: i64 = 5 * 60;
+
+/// Admin roles, ordered from least to most privileged.
+/// Viewers can read, editors can also manage projects and contacts,
+/// owners can also manage other admin users.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Role> {
+        match value {
+            "viewer" => Some(Role::Viewer),
+            "editor" => Some(Role::Editor),
+            "owner" => Some(Role::Owner),
+            _ => None,
+        }
+    }
+
+    pub fn scopes(&self) -> &'static [Scope] {
+        match self {
+            Role::Viewer => &[Scope::ContactsRead],
+            Role::Editor => &[Scope::ContactsRead, Scope::ContactsWrite, Scope::ProjectsWrite],
+            Role::Owner => &[Scope::ContactsRead, Scope::ContactsWrite, Scope::ProjectsWrite],
+        }
+    }
+}
+
+/// Permissions checked by routes that accept both admin sessions and API keys.
+/// Admins get the scopes of their role; API keys carry an explicit list.
+/// Managing admins and API keys is owner-only and never reachable with a key.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "contacts:read")]
+    ContactsRead,
+    #[serde(rename = "contacts:write")]
+    ContactsWrite,
+    #[serde(rename = "projects:write")]
+    ProjectsWrite,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ContactsRead => "contacts:read",
+            Scope::ContactsWrite => "contacts:write",
+            Scope::ProjectsWrite => "projects:write",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Scope> {
+        match value {
+            "contacts:read" => Some(Scope::ContactsRead),
+            "contacts:write" => Some(Scope::ContactsWrite),
+            "projects:write" => Some(Scope::ProjectsWrite),
+            _ => None,
+        }
+    }
+}
+
+/// What a token grants. Only session tokens authenticate API requests;
+/// two-factor tokens just prove the password step of a login succeeded.
+#[derive(Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    #[default]
+    Session,
+    TwoFactor,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: i64,
+    pub iat: i64,
+    pub exp: i64,
+    #[serde(default)]
+    pub kind: TokenKind,
+    // The server-side session a session token belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
+
+/// Loads the token signing secret from ADMIN_TOKEN_SECRET.
+/// Changing the secret invalidates every token signed with the old one.
+pub fn load_secret() -> Vec<u8> {
+    match std::env::var("ADMIN_TOKEN_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+        _ => {
+            tracing::warn!("ADMIN_TOKEN_SECRET not set, using a random secret (sessions end on restart)");
+            use rand::RngCore;
+            let mut secret = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut secret);
+            secret
+        }
+    }
+}
+
+pub fn token_ttl_secs() -> i64 {
+    std::env::var("ADMIN_TOKEN_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_TOKEN_TTL_SECS)
+}
+
+fn sign(secret: &[u8], payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Mints an access token for one of an admin's sessions.
+pub fn issue_token(secret: &[u8], user_id: i64, session_id: &str) -> String {
+    issue(secret, user_id, TokenKind::Session, Some(session_id.to_string()), token_ttl_secs())
+}
+
+/// Mints the short-lived token handed out between the two steps of a 2FA login.
+pub fn issue_two_factor_challenge(secret: &[u8], user_id: i64) -> String {
+    issue(secret, user_id, TokenKind::TwoFactor, None, TWO_FACTOR_CHALLENGE_TTL_SECS)
+}
+
+/// Tokens have the form `<base64url claims>.<base64url HMAC-SHA256>`.
+fn issue(secret: &[u8], user_id: i64, kind: TokenKind, sid: Option<String>, ttl_secs: i64) -> String {
+    let now = chrono::Utc::now().timestamp();
+    let claims = Claims {
+        sub: user_id,
+        iat: now,
+        exp: now + ttl_secs,
+        kind,
+        sid,
+    };
+
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap());
+    let signature = URL_SAFE_NO_PAD.encode(sign(secret, &payload).finalize().into_bytes());
+
+    format!("{}.{}", payload, signature)
+}
+
+/// Checks the signature, expiry and kind of a token and returns its claims.
+pub fn verify_token(secret: &[u8], token: &str, kind: TokenKind) -> Option<Claims> {
+    let (payload, signature) = token.split_once('.')?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+    sign(secret, payload).verify_slice(&signature).ok()?;
+
+    let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+    if claims.exp <= chrono::Utc::now().timestamp() || claims.kind != kind {
+        return None;
+    }
+
+    Some(claims)
+}
+
+/// Signs `value` for one `purpose`, so a signature made for one use can't be replayed in another.
+pub fn sign_value(secret: &[u8], purpose: &str, value: &str) -> String {
+    URL_SAFE_NO_PAD.encode(sign(secret, &format!("{}.{}", purpose, value)).finalize().into_bytes())
+}
+
+/// Checks a signature from [`sign_value`] in constant time.
+pub fn verify_value(secret: &[u8], purpose: &str, value: &str, signature: &str) -> bool {
+    let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+    sign(secret, &format!("{}.{}", purpose, value)).verify_slice(&signature).is_ok()
+}
+
+/// The CSRF token for a cookie-mode session. It's derived from the session id, so it
+/// stays the same across refreshes and can't be reused for anyone else's session.
+pub fn csrf_token(secret: &[u8], session_id: &str) -> String {
+    sign_value(secret, "csrf", session_id)
+}
+
+pub fn verify_csrf(secret: &[u8], session_id: &str, presented: Option<&str>) -> bool {
+    presented.is_some_and(|token| verify_value(secret, "csrf", session_id, token))
+}
+
+/// An authenticated admin, extracted from an `Authorization: Bearer <token>` header
+/// or, for cookie-mode sessions, the session cookie plus a CSRF header on unsafe methods.
+/// Accounts that still have to change their password are rejected with 403.
+pub struct AdminUser {
+    pub id: i64,
+    pub username: String,
+    pub role: Role,
+}
+
+impl AdminUser {
+    /// Rejects the request with 403 unless the admin has at least `role`.
+    pub fn require(&self, role: Role) -> Result<(), StatusCode> {
+        if self.role >= role {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+/// Like [`AdminUser`], but also admits accounts with a pending password change.
+/// Only the routes needed to complete that change should accept it.
+pub struct AdminIdentity {
+    pub id: i64,
+    pub username: String,
+    pub role: Role,
+    pub must_change_password: bool,
+    pub session_id: String,
+}
+
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AdminIdentity {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let (token, from_cookie) = match bearer_token(parts) {
+            Some(token) => (token, false),
+            None => (cookies::get(&parts.headers, cookies::SESSION_COOKIE).ok_or(StatusCode::UNAUTHORIZED)?, true),
+        };
+
+        let claims = verify_token(&state.token_secret, token, TokenKind::Session).ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let session_id = claims.sid.ok_or(StatusCode::UNAUTHORIZED)?;
+
+        // Browsers attach cookies to cross-site requests too, so anything that changes
+        // state has to prove it came from a page that could read the CSRF cookie
+        let safe_method = matches!(parts.method, Method::GET | Method::HEAD | Method::OPTIONS);
+        if from_cookie && !safe_method {
+            let presented = parts.headers.get(cookies::CSRF_HEADER).and_then(|v| v.to_str().ok());
+            if !verify_csrf(&state.token_secret, &session_id, presented) {
+                return Err(StatusCode::FORBIDDEN);
+            }
+        }
+
+        let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        // Logging out or killing a session takes effect immediately, not when the token expires
+        if !sessions::touch(&conn, &session_id, claims.sub).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        // The account may have been removed or disabled since the token was issued
+        let (username, role, must_change_password): (String, String, bool) = conn.query_row(
+            "SELECT username, role, must_change_password FROM admin_users WHERE id = ?1 AND disabled = 0",
+            [claims.sub],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, i64>(2)? != 0)),
+        ).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+        let role = Role::parse(&role).ok_or(StatusCode::UNAUTHORIZED)?;
+
+        Ok(AdminIdentity { id: claims.sub, username, role, must_change_password, session_id })
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AdminUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let identity = AdminIdentity::from_request_parts(parts, state).await?;
+
+        if identity.must_change_password {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        Ok(AdminUser { id: identity.id, username: identity.username, role: identity.role })
+    }
+}
+
+/// Who made a request to a route that accepts both admin sessions and API keys.
+pub enum Actor {
+    Admin { id: i64, username: String },
+    ApiKey { id: i64, name: String },
+}
+
+impl Actor {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Actor::Admin { .. } => "admin",
+            Actor::ApiKey { .. } => "api_key",
+        }
+    }
+
+    pub fn id(&self) -> i64 {
+        match self {
+            Actor::Admin { id, .. } | Actor::ApiKey { id, .. } => *id,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Actor::Admin { username, .. } => username,
+            Actor::ApiKey { name, .. } => name,
+        }
+    }
+}
+
+impl std::fmt::Display for Actor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Actor::Admin { id, username } => write!(f, "admin {} ({})", id, username),
+            Actor::ApiKey { id, name } => write!(f, "api key {} ({})", id, name),
+        }
+    }
+}
+
+/// An authenticated caller, extracted from `Authorization: Bearer <token>` where the
+/// token is either an admin session token or an API key (`pk_...`), or from a
+/// cookie-mode admin session.
+pub struct Principal {
+    pub actor: Actor,
+    scopes: Vec<Scope>,
+}
+
+impl Principal {
+    /// Rejects the request with 403 unless the caller holds `scope`.
+    pub fn require(&self, scope: Scope) -> Result<(), StatusCode> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Principal {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        if let Some(token) = bearer_token(parts).filter(|token| token.starts_with(api_keys::KEY_PREFIX)) {
+            let conn = state.conn.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            let key = api_keys::verify(&conn, token)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::UNAUTHORIZED)?;
+
+            return Ok(Principal {
+                actor: Actor::ApiKey { id: key.id, name: key.name },
+                scopes: key.scopes,
+            });
+        }
+
+        let admin = AdminUser::from_request_parts(parts, state).await?;
+
+        Ok(Principal {
+            scopes: admin.role.scopes().to_vec(),
+            actor: Actor::Admin { id: admin.id, username: admin.username },
+        })
+    }
+}
+
+/// The client's IP address: the TCP peer, unless CLIENT_IP_HEADER names a header set by a
+/// trusted proxy in front of the app (`Fly-Client-IP` on Fly.io). Without that setting a
+/// forwarded header is just something the client chose to send, so it is ignored.
+pub struct ClientIp(pub String);
+
+impl ClientIp {
+    fn from_parts(parts: &Parts, header_name: Option<&str>) -> Self {
+        if let Some(ip) = header_name
+            .and_then(|name| parts.headers.get(name))
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|ip| !ip.is_empty())
+        {
+            return ClientIp(ip.to_string());
+        }
+
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        ClientIp(ip)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header_name = std::env::var("CLIENT_IP_HEADER").ok().filter(|name| !name.trim().is_empty());
+        Ok(ClientIp::from_parts(parts, header_name.as_deref().map(str::trim)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"test secret";
+
+    #[test]
+    fn issued_token_verifies() {
+        let token = issue_token(SECRET, 7, "session-1");
+        let claims = verify_token(SECRET, &token, TokenKind::Session).expect("fresh token is valid");
+        assert_eq!(claims.sub, 7);
+        assert_eq!(claims.sid.as_deref(), Some("session-1"));
+    }
+
+    #[test]
+    fn tampered_claims_are_rejected() {
+        let token = issue_token(SECRET, 7, "session-1");
+        let (_, signature) = token.split_once('.').unwrap();
+
+        let forged = Claims { sub: 1, iat: 0, exp: i64::MAX, kind: TokenKind::Session, sid: Some("session-1".to_string()) };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+
+        assert!(verify_token(SECRET, &format!("{}.{}", payload, signature), TokenKind::Session).is_none());
+    }
+
+    #[test]
+    fn tampered_or_missing_signature_is_rejected() {
+        let token = issue_token(SECRET, 7, "session-1");
+        let (payload, _) = token.split_once('.').unwrap();
+        let wrong = URL_SAFE_NO_PAD.encode([0u8; 32]);
+
+        assert!(verify_token(SECRET, &format!("{}.{}", payload, wrong), TokenKind::Session).is_none());
+        assert!(verify_token(SECRET, payload, TokenKind::Session).is_none());
+        assert!(verify_token(SECRET, &format!("{}.not base64!", payload), TokenKind::Session).is_none());
+        assert!(verify_token(b"another secret", &token, TokenKind::Session).is_none());
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let token = issue(SECRET, 7, TokenKind::Session, Some("session-1".to_string()), -1);
+        assert!(verify_token(SECRET, &token, TokenKind::Session).is_none());
+    }
+
+    #[test]
+    fn token_of_another_kind_is_rejected() {
+        let challenge = issue_two_factor_challenge(SECRET, 7);
+        assert!(verify_token(SECRET, &challenge, TokenKind::Session).is_none());
+        assert!(verify_token(SECRET, &challenge, TokenKind::TwoFactor).is_some());
+    }
+
+    #[test]
+    fn signed_value_is_bound_to_value_and_purpose() {
+        let signature = sign_value(SECRET, "contact-form", "42");
+
+        assert!(verify_value(SECRET, "contact-form", "42", &signature));
+        assert!(!verify_value(SECRET, "contact-form", "43", &signature));
+        assert!(!verify_value(SECRET, "csrf", "42", &signature));
+        assert!(!verify_value(b"another secret", "contact-form", "42", &signature));
+        assert!(!verify_value(SECRET, "contact-form", "42", "not base64!"));
+    }
+
+    #[test]
+    fn csrf_token_only_fits_its_own_session() {
+        let token = csrf_token(SECRET, "session-1");
+
+        assert!(verify_csrf(SECRET, "session-1", Some(&token)));
+        assert!(!verify_csrf(SECRET, "session-2", Some(&token)));
+        assert!(!verify_csrf(SECRET, "session-1", None));
+        assert!(!verify_csrf(SECRET, "session-1", Some("")));
+    }
+
+    fn request_parts() -> Parts {
+        let (mut parts, _) = axum::http::Request::builder()
+            .header("fly-client-ip", "203.0.113.9")
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts.extensions.insert(ConnectInfo(SocketAddr::from(([198, 51, 100, 1], 443))));
+        parts
+    }
+
+    #[test]
+    fn forwarded_ip_is_only_trusted_when_configured() {
+        let parts = request_parts();
+
+        assert_eq!(ClientIp::from_parts(&parts, None).0, "198.51.100.1");
+        assert_eq!(ClientIp::from_parts(&parts, Some("Fly-Client-IP")).0, "203.0.113.9");
+        assert_eq!(ClientIp::from_parts(&parts, Some("X-Real-IP")).0, "198.51.100.1");
+    }
+}
@@ -0,0 +1,274 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::collections::HashSet;
+
+// Naive-Bayes spam filter over the words of a contact submission. Statistics are
+// document frequencies: for each token, how many spam and how many ham (legitimate)
+// messages contained it. Admin marks feed it; `retrain` rebuilds it from scratch.
+
+// No verdict until each class has this many examples; a handful of marks is just noise
+const MIN_DOCUMENTS_PER_CLASS: i64 = 5;
+// Probability at which the filter files a message as spam unless SPAM_PROBABILITY_THRESHOLD says otherwise
+const DEFAULT_PROBABILITY_THRESHOLD: f64 = 0.9;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Label {
+    Spam,
+    Ham,
+}
+
+impl Label {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Label::Spam => "spam",
+            Label::Ham => "ham",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Label> {
+        match value {
+            "spam" => Some(Label::Spam),
+            "ham" => Some(Label::Ham),
+            _ => None,
+        }
+    }
+
+    fn column(&self) -> &'static str {
+        match self {
+            Label::Spam => "spam_count",
+            Label::Ham => "ham_count",
+        }
+    }
+}
+
+pub fn probability_threshold() -> f64 {
+    std::env::var("SPAM_PROBABILITY_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_PROBABILITY_THRESHOLD)
+}
+
+/// The text a contact is classified on: who sent it and what they wrote.
+pub fn document(name: &str, email: &str, subject: &str, message: &str) -> String {
+    let domain = email.rsplit_once('@').map(|(_, domain)| domain).unwrap_or("");
+    format!("{} {} {} {}", name, domain, subject, message)
+}
+
+/// Distinct lowercase words of 2 to 30 characters. Links collapse to their host so
+/// "https://spam.example/path?x" and "https://spam.example/other" count as the same signal.
+fn tokenize(text: &str) -> HashSet<String> {
+    let mut tokens = HashSet::new();
+
+    for word in text.split_whitespace() {
+        let lower = word.to_lowercase();
+        if let Some(rest) = lower.strip_prefix("http://").or_else(|| lower.strip_prefix("https://")) {
+            let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+            tokens.insert(format!("host:{}", host.trim_start_matches("www.")));
+            continue;
+        }
+
+        for part in lower.split(|c: char| !c.is_alphanumeric() && c != '\'') {
+            let part = part.trim_matches('\'');
+            let len = part.chars().count();
+            if (2..=30).contains(&len) {
+                tokens.insert(part.to_string());
+            }
+        }
+    }
+
+    tokens
+}
+
+fn documents(conn: &Connection, label: Label) -> Result<i64> {
+    conn.query_row(
+        "SELECT documents FROM spam_documents WHERE label = ?1",
+        [label.as_str()],
+        |row| row.get(0),
+    ).optional().map(|count| count.unwrap_or(0))
+}
+
+/// Adds (`delta` = 1) or removes (`delta` = -1) one labelled document from the statistics.
+pub fn learn(conn: &Connection, text: &str, label: Label, delta: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO spam_documents (label, documents) VALUES (?1, MAX(?2, 0))
+         ON CONFLICT(label) DO UPDATE SET documents = MAX(documents + ?2, 0)",
+        params![label.as_str(), delta],
+    )?;
+
+    let column = label.column();
+    let sql = format!(
+        "INSERT INTO spam_tokens (token, {column}) VALUES (?1, MAX(?2, 0))
+         ON CONFLICT(token) DO UPDATE SET {column} = MAX({column} + ?2, 0)"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    for token in tokenize(text) {
+        stmt.execute(params![token, delta])?;
+    }
+
+    conn.execute("DELETE FROM spam_tokens WHERE spam_count = 0 AND ham_count = 0", [])?;
+    Ok(())
+}
+
+/// The probability that `text` is spam, or `None` while there isn't enough training data.
+pub fn probability(conn: &Connection, text: &str) -> Result<Option<f64>> {
+    let spam_docs = documents(conn, Label::Spam)?;
+    let ham_docs = documents(conn, Label::Ham)?;
+    if spam_docs < MIN_DOCUMENTS_PER_CLASS || ham_docs < MIN_DOCUMENTS_PER_CLASS {
+        return Ok(None);
+    }
+
+    // Work in log odds so long messages don't underflow
+    let mut log_odds = (spam_docs as f64).ln() - (ham_docs as f64).ln();
+
+    let mut stmt = conn.prepare("SELECT spam_count, ham_count FROM spam_tokens WHERE token = ?1")?;
+    for token in tokenize(text) {
+        let counts: Option<(i64, i64)> = stmt
+            .query_row([&token], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?;
+
+        // Tokens never seen in training say nothing either way
+        if let Some((spam_count, ham_count)) = counts {
+            // Laplace smoothing keeps a single unseen-in-one-class token from deciding the verdict
+            let p_spam = (spam_count as f64 + 1.0) / (spam_docs as f64 + 2.0);
+            let p_ham = (ham_count as f64 + 1.0) / (ham_docs as f64 + 2.0);
+            log_odds += p_spam.ln() - p_ham.ln();
+        }
+    }
+
+    let probability = 1.0 / (1.0 + (-log_odds).exp());
+    Ok(Some((probability * 10_000.0).round() / 10_000.0))
+}
+
+pub struct RetrainSummary {
+    pub spam_documents: i64,
+    pub ham_documents: i64,
+    pub reclassified: usize,
+}
+
+/// Rebuilds the statistics from every labelled contact, then rescores all contacts.
+pub fn retrain(conn: &Connection) -> Result<RetrainSummary> {
+    conn.execute("DELETE FROM spam_tokens", [])?;
+    conn.execute("DELETE FROM spam_documents", [])?;
+
+    let mut stmt = conn.prepare(
+        "SELECT name, email, subject, message, spam_label FROM contacts WHERE spam_label IS NOT NULL"
+    )?;
+    let labelled: Vec<(String, Label)> = stmt.query_map([], |row| {
+        Ok((
+            document(&row.get::<_, String>(0)?, &row.get::<_, String>(1)?, &row.get::<_, String>(2)?, &row.get::<_, String>(3)?),
+            row.get::<_, String>(4)?,
+        ))
+    })?
+        .filter_map(|r| r.ok())
+        .filter_map(|(text, label)| Label::parse(&label).map(|label| (text, label)))
+        .collect();
+
+    for (text, label) in &labelled {
+        learn(conn, text, *label, 1)?;
+    }
+
+    let mut stmt = conn.prepare("SELECT id, name, email, subject, message FROM contacts")?;
+    let contacts: Vec<(i64, String)> = stmt.query_map([], |row| {
+        Ok((
+            row.get(0)?,
+            document(&row.get::<_, String>(1)?, &row.get::<_, String>(2)?, &row.get::<_, String>(3)?, &row.get::<_, String>(4)?),
+        ))
+    })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    for (id, text) in &contacts {
+        conn.execute(
+            "UPDATE contacts SET spam_probability = ?1 WHERE id = ?2",
+            params![probability(conn, text)?, id],
+        )?;
+    }
+
+    Ok(RetrainSummary {
+        spam_documents: documents(conn, Label::Spam)?,
+        ham_documents: documents(conn, Label::Ham)?,
+        reclassified: contacts.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::AppState;
+
+    fn db() -> Connection {
+        AppState::migrate(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn train(conn: &Connection) {
+        for i in 0..MIN_DOCUMENTS_PER_CLASS {
+            learn(conn, &format!("cheap backlinks and seo services https://spam{}.example/offer", i), Label::Spam, 1).unwrap();
+            learn(conn, &format!("hello, could we talk about project {} next week", i), Label::Ham, 1).unwrap();
+        }
+    }
+
+    #[test]
+    fn links_collapse_to_their_host() {
+        let tokens = tokenize("See https://www.Spam.example/path?x=1 and http://spam.example#top a I'm");
+
+        assert!(tokens.contains("host:spam.example"));
+        assert!(tokens.contains("see"));
+        assert!(tokens.contains("i'm"));
+        assert!(!tokens.contains("a"));
+        assert_eq!(tokens.iter().filter(|t| t.starts_with("host:")).count(), 1);
+    }
+
+    #[test]
+    fn no_verdict_until_both_classes_have_examples() {
+        let conn = db();
+        for _ in 0..MIN_DOCUMENTS_PER_CLASS {
+            learn(&conn, "cheap backlinks", Label::Spam, 1).unwrap();
+        }
+        learn(&conn, "hello there", Label::Ham, 1).unwrap();
+
+        assert_eq!(probability(&conn, "cheap backlinks").unwrap(), None);
+    }
+
+    #[test]
+    fn learned_words_decide_the_verdict() {
+        let conn = db();
+        train(&conn);
+
+        assert!(probability(&conn, "buy seo backlinks").unwrap().unwrap() > 0.9);
+        assert!(probability(&conn, "can we talk about your project").unwrap().unwrap() < 0.1);
+        assert_eq!(probability(&conn, "entirely unseen words").unwrap(), Some(0.5));
+    }
+
+    #[test]
+    fn unlearning_removes_a_document_completely() {
+        let conn = db();
+        learn(&conn, "cheap backlinks", Label::Spam, 1).unwrap();
+        learn(&conn, "cheap backlinks", Label::Spam, -1).unwrap();
+
+        let tokens: i64 = conn.query_row("SELECT COUNT(*) FROM spam_tokens", [], |row| row.get(0)).unwrap();
+        assert_eq!(tokens, 0);
+        assert_eq!(documents(&conn, Label::Spam).unwrap(), 0);
+
+        // Counts never go negative, however often a document is unlearned
+        learn(&conn, "cheap backlinks", Label::Spam, -1).unwrap();
+        assert_eq!(documents(&conn, Label::Spam).unwrap(), 0);
+    }
+
+    #[test]
+    fn retrain_rebuilds_from_labelled_contacts() {
+        let conn = db();
+        learn(&conn, "stale statistics", Label::Ham, 1).unwrap();
+        conn.execute(
+            "INSERT INTO contacts (name, email, subject, message, spam_label) VALUES
+                ('Bot', 'bot@spam.example', 'SEO', 'cheap backlinks', 'spam'),
+                ('Jane', 'jane@example.com', 'Hello', 'about your project', 'ham'),
+                ('Joe', 'joe@example.com', 'Hi', 'unlabelled', NULL)",
+            [],
+        ).unwrap();
+
+        let summary = retrain(&conn).unwrap();
+        assert_eq!((summary.spam_documents, summary.ham_documents, summary.reclassified), (1, 1, 3));
+
+        let stale: i64 = conn.query_row("SELECT COUNT(*) FROM spam_tokens WHERE token = 'stale'", [], |row| row.get(0)).unwrap();
+        assert_eq!(stale, 0);
+    }
+}
@@ -0,0 +1,61 @@
+use axum::http::{header, HeaderMap, HeaderName};
+use axum::response::AppendHeaders;
+use crate::auth;
+use crate::sessions::{self, SessionTokens};
+
+// Cookie-mode admin sessions. The access and refresh tokens live in HttpOnly cookies
+// that page scripts can't read; the CSRF cookie is readable so the dashboard can echo
+// it back in the CSRF header on every state-changing request.
+pub const SESSION_COOKIE: &str = "admin_session";
+pub const REFRESH_COOKIE: &str = "admin_refresh";
+pub const CSRF_COOKIE: &str = "admin_csrf";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+// The refresh cookie is only ever sent to the refresh endpoint
+const REFRESH_PATH: &str = "/api/admin/refresh";
+
+pub type SetCookies = AppendHeaders<Vec<(HeaderName, String)>>;
+
+/// Returns the value of the named cookie from the request's Cookie headers.
+pub fn get<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn build(name: &str, value: &str, path: &str, max_age: i64, http_only: bool) -> String {
+    format!(
+        "{}={}; Path={}; Max-Age={}; Secure; SameSite=Strict{}",
+        name,
+        value,
+        path,
+        max_age,
+        if http_only { "; HttpOnly" } else { "" }
+    )
+}
+
+/// Set-Cookie headers that put a freshly started or refreshed session in the browser.
+pub fn set_session(token_secret: &[u8], tokens: &SessionTokens) -> SetCookies {
+    let refresh_ttl = sessions::refresh_ttl_secs();
+    let csrf_token = auth::csrf_token(token_secret, &tokens.session_id);
+
+    AppendHeaders(vec![
+        (header::SET_COOKIE, build(SESSION_COOKIE, &tokens.access_token, "/api", auth::token_ttl_secs(), true)),
+        (header::SET_COOKIE, build(REFRESH_COOKIE, &tokens.refresh_token, REFRESH_PATH, refresh_ttl, true)),
+        (header::SET_COOKIE, build(CSRF_COOKIE, &csrf_token, "/", refresh_ttl, false)),
+    ])
+}
+
+/// Set-Cookie headers that remove every session cookie.
+pub fn clear_session() -> SetCookies {
+    AppendHeaders(vec![
+        (header::SET_COOKIE, build(SESSION_COOKIE, "", "/api", 0, true)),
+        (header::SET_COOKIE, build(REFRESH_COOKIE, "", REFRESH_PATH, 0, true)),
+        (header::SET_COOKIE, build(CSRF_COOKIE, "", "/", 0, false)),
+    ])
+}
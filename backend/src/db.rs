@@ -1,13 +1,25 @@
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, OptionalExtension, Result};
 use std::sync::Mutex;
 
 pub struct AppState {
     pub conn: Mutex<Connection>,
+    pub token_secret: Vec<u8>,
 }
 
 impl AppState {
     pub fn new() -> Result<Self> {
-        let conn = Connection::open("portfolio.db")?;
+        let conn = Self::migrate(Connection::open("portfolio.db")?)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            token_secret: crate::auth::load_secret(),
+        })
+    }
+
+    /// Creates whatever tables and columns the database is missing, then hands the connection back.
+    pub fn migrate(conn: Connection) -> Result<Connection> {
+        // SQLite leaves foreign key enforcement (and ON DELETE CASCADE) off by default
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
 
         // Contact form submissions
         conn.execute(
@@ -18,7 +30,14 @@ impl AppState {
                 subject TEXT NOT NULL,
                 message TEXT NOT NULL,
                 created_at TEXT DEFAULT CURRENT_TIMESTAMP,
-                read INTEGER DEFAULT 0
+                read INTEGER DEFAULT 0,
+                status TEXT NOT NULL DEFAULT 'inbox',
+                spam_score REAL NOT NULL DEFAULT 0,
+                spam_probability REAL,
+                spam_label TEXT,
+                archived_at TEXT,
+                message_id TEXT,
+                acknowledged_at TEXT
             )",
             [],
         )?;
@@ -45,32 +64,366 @@ impl AppState {
             "CREATE TABLE IF NOT EXISTS admin_users (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 username TEXT NOT NULL UNIQUE,
+                email TEXT,
                 password_hash TEXT NOT NULL,
+                role TEXT NOT NULL DEFAULT 'viewer',
+                disabled INTEGER NOT NULL DEFAULT 0,
+                must_change_password INTEGER NOT NULL DEFAULT 0,
+                totp_secret TEXT,
+                totp_enabled INTEGER NOT NULL DEFAULT 0,
+                totp_last_step INTEGER,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Single-use 2FA recovery codes, stored as SHA-256 hashes
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS admin_recovery_codes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL REFERENCES admin_users(id) ON DELETE CASCADE,
+                code_hash TEXT NOT NULL,
+                used_at TEXT
+            )",
+            [],
+        )?;
+
+        // Admin sessions; access tokens name their session so it can be revoked server-side
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS admin_sessions (
+                id TEXT PRIMARY KEY,
+                user_id INTEGER NOT NULL REFERENCES admin_users(id) ON DELETE CASCADE,
+                refresh_token_hash TEXT NOT NULL,
+                user_agent TEXT NOT NULL,
+                ip TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                last_seen_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                expires_at INTEGER NOT NULL,
+                revoked_at TEXT
+            )",
+            [],
+        )?;
+
+        // Emailed password reset links; single use, and only a hash of the token is stored
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS password_resets (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL REFERENCES admin_users(id) ON DELETE CASCADE,
+                token_hash TEXT NOT NULL UNIQUE,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                expires_at INTEGER NOT NULL,
+                used_at TEXT
+            )",
+            [],
+        )?;
+
+        // API keys for automation; only a hash of each key is stored
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS api_keys (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                lookup_id TEXT NOT NULL UNIQUE,
+                key_hash TEXT NOT NULL,
+                scopes TEXT NOT NULL,
+                created_by INTEGER REFERENCES admin_users(id) ON DELETE SET NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                last_used_at TEXT,
+                revoked_at TEXT
+            )",
+            [],
+        )?;
+
+        // Who changed what; `changes` holds a JSON before/after diff of the target
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                actor_kind TEXT NOT NULL,
+                actor_id INTEGER,
+                actor_name TEXT NOT NULL,
+                action TEXT NOT NULL,
+                target_id INTEGER,
+                client_ip TEXT NOT NULL,
+                changes TEXT NOT NULL,
                 created_at TEXT DEFAULT CURRENT_TIMESTAMP
             )",
             [],
         )?;
 
-        // Insert default admin if not exists (password: admin123 - CHANGE THIS!)
-        // In production, use a proper hashed password
-        let admin_exists: bool = conn.query_row(
-            "SELECT 1 FROM admin_users WHERE username = 'admin' LIMIT 1",
+        // Naive-Bayes spam filter statistics, learned from contacts admins marked as spam or ham
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS spam_tokens (
+                token TEXT PRIMARY KEY,
+                spam_count INTEGER NOT NULL DEFAULT 0,
+                ham_count INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS spam_documents (
+                label TEXT PRIMARY KEY,
+                documents INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Answers sent to contact submitters; together with the submission they form one email thread
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS contact_replies (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                contact_id INTEGER NOT NULL REFERENCES contacts(id) ON DELETE CASCADE,
+                message_id TEXT NOT NULL UNIQUE,
+                in_reply_to TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                body TEXT NOT NULL,
+                sent_by TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Outgoing email, delivered by the outbox worker. status is 'pending', 'sent' or
+        // 'failed' (out of attempts); next_attempt_at is a unix timestamp
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS email_outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                from_name TEXT NOT NULL,
+                recipient TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                body TEXT NOT NULL,
+                headers TEXT NOT NULL DEFAULT '[]',
+                html_body TEXT,
+                status TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at INTEGER NOT NULL,
+                last_error TEXT,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                sent_at TEXT
+            )",
+            [],
+        )?;
+
+        // Admin overrides of the bundled email templates
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS email_templates (
+                name TEXT PRIMARY KEY,
+                subject TEXT NOT NULL,
+                text_body TEXT NOT NULL,
+                html_body TEXT NOT NULL,
+                updated_by TEXT NOT NULL,
+                updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Outgoing webhook subscriptions. events is a space-separated list of event names;
+        // the secret signs every delivery, so it's kept in the clear
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS webhooks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                url TEXT NOT NULL,
+                description TEXT NOT NULL DEFAULT '',
+                events TEXT NOT NULL,
+                format TEXT NOT NULL DEFAULT 'json',
+                secret TEXT NOT NULL,
+                active INTEGER NOT NULL DEFAULT 1,
+                created_by TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+            )",
             [],
-            |_| Ok(true),
-        ).unwrap_or(false);
+        )?;
+
+        // One row per event per subscription, delivered by the webhook worker. status is
+        // 'pending', 'delivered' or 'failed' (out of attempts); next_attempt_at is a unix timestamp
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                webhook_id INTEGER NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+                event TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at INTEGER NOT NULL,
+                response_status INTEGER,
+                last_error TEXT,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                delivered_at TEXT
+            )",
+            [],
+        )?;
+
+        // Failed admin login tracking, keyed by username and by client IP
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS login_attempts (
+                key TEXT PRIMARY KEY,
+                failures INTEGER NOT NULL,
+                last_failure_at INTEGER NOT NULL,
+                locked_until INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Databases created before password rotation was enforced lack this column
+        add_column_if_missing(&conn, "admin_users", "must_change_password", "INTEGER NOT NULL DEFAULT 0")?;
+
+        // Before roles existed every admin had full access, so existing accounts become owners
+        add_column_if_missing(&conn, "admin_users", "role", "TEXT NOT NULL DEFAULT 'owner'")?;
+        add_column_if_missing(&conn, "admin_users", "disabled", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "admin_users", "totp_secret", "TEXT")?;
+        add_column_if_missing(&conn, "admin_users", "totp_enabled", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "admin_users", "totp_last_step", "INTEGER")?;
+        add_column_if_missing(&conn, "admin_users", "email", "TEXT")?;
+        // 'inbox' or 'spam'; spam is kept for review but never emailed
+        add_column_if_missing(&conn, "contacts", "status", "TEXT NOT NULL DEFAULT 'inbox'")?;
+        add_column_if_missing(&conn, "contacts", "spam_score", "REAL NOT NULL DEFAULT 0")?;
+        // The classifier's verdict, and the admin's 'spam'/'ham' mark it learns from
+        add_column_if_missing(&conn, "contacts", "spam_probability", "REAL")?;
+        add_column_if_missing(&conn, "contacts", "spam_label", "TEXT")?;
+        add_column_if_missing(&conn, "contacts", "archived_at", "TEXT")?;
+        // Submissions from before replies existed get a Message-ID when first answered
+        add_column_if_missing(&conn, "contacts", "message_id", "TEXT")?;
+        // When the submitter was sent an automatic acknowledgement
+        add_column_if_missing(&conn, "contacts", "acknowledged_at", "TEXT")?;
+
+        Ok(conn)
+    }
+}
+
+// Older releases seeded an owner account with these credentials
+const LEGACY_ADMIN_USERNAME: &str = "admin";
+const LEGACY_ADMIN_PASSWORD: &str = "admin123";
+
+impl AppState {
+    /// Creates the first owner account from ADMIN_USERNAME and either ADMIN_PASSWORD or
+    /// ADMIN_PASSWORD_HASH (a bcrypt hash) when the database has no admins yet.
+    /// With APP_ENV=production, a database without admins and without that config is an error.
+    ///
+    /// An account still on the old default admin/admin123 credentials is taken over by the
+    /// configured ones, with its sessions revoked. Without that config, production refuses to
+    /// start; anywhere else the account has to change its password before doing anything.
+    pub fn bootstrap_admin(&self) -> std::result::Result<(), String> {
+        let conn = self.conn.lock().map_err(|_| "database lock poisoned".to_string())?;
+
+        if let Some(legacy_id) = legacy_admin(&conn).map_err(|e| e.to_string())? {
+            return match configured_admin()? {
+                Some((username, password_hash)) => {
+                    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+                    tx.execute(
+                        "UPDATE admin_users SET username = ?1, password_hash = ?2, must_change_password = 0 WHERE id = ?3",
+                        rusqlite::params![username, password_hash, legacy_id],
+                    ).map_err(|e| format!("Couldn't replace the default admin account: {}", e))?;
+                    tx.execute(
+                        "UPDATE admin_sessions SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = ?1 AND revoked_at IS NULL",
+                        [legacy_id],
+                    ).map_err(|e| e.to_string())?;
+                    tx.commit().map_err(|e| e.to_string())?;
 
-        if !admin_exists {
-            // Default password: admin123
-            // You should change this immediately after first login
-            let password_hash = bcrypt::hash("admin123", bcrypt::DEFAULT_COST).unwrap();
-            conn.execute(
-                "INSERT INTO admin_users (username, password_hash) VALUES (?1, ?2)",
-                ["admin", &password_hash],
-            )?;
+                    tracing::warn!("Replaced the default admin/admin123 credentials with account {}", username);
+                    Ok(())
+                }
+                None if is_production() => Err(
+                    "The admin account still has the default password admin123; set ADMIN_USERNAME and \
+                     ADMIN_PASSWORD or ADMIN_PASSWORD_HASH to replace it".to_string(),
+                ),
+                None => {
+                    conn.execute("UPDATE admin_users SET must_change_password = 1 WHERE id = ?1", [legacy_id])
+                        .map_err(|e| e.to_string())?;
+                    tracing::warn!("The admin account still has the default password admin123 and must change it");
+                    Ok(())
+                }
+            };
         }
 
-        Ok(Self {
-            conn: Mutex::new(conn),
-        })
+        let admin_count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM admin_users", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if admin_count > 0 {
+            return Ok(());
+        }
+
+        let Some((username, password_hash)) = configured_admin()? else {
+            if is_production() {
+                return Err(
+                    "No admin account exists; set ADMIN_USERNAME and ADMIN_PASSWORD or ADMIN_PASSWORD_HASH".to_string(),
+                );
+            }
+            tracing::warn!(
+                "No admin account exists; set ADMIN_USERNAME and ADMIN_PASSWORD or ADMIN_PASSWORD_HASH to create one"
+            );
+            return Ok(());
+        };
+
+        conn.execute(
+            "INSERT INTO admin_users (username, password_hash, role) VALUES (?1, ?2, 'owner')",
+            [&username, &password_hash],
+        ).map_err(|e| e.to_string())?;
+
+        tracing::info!("Created initial admin account {}", username);
+        Ok(())
     }
 }
+
+fn is_production() -> bool {
+    std::env::var("APP_ENV").is_ok_and(|env| env == "production")
+}
+
+/// The id of the account still using the old seeded admin/admin123 credentials, if any.
+fn legacy_admin(conn: &Connection) -> Result<Option<i64>> {
+    let account: Option<(i64, String)> = conn.query_row(
+        "SELECT id, password_hash FROM admin_users WHERE username = ?1",
+        [LEGACY_ADMIN_USERNAME],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
+
+    Ok(account
+        .filter(|(_, hash)| bcrypt::verify(LEGACY_ADMIN_PASSWORD, hash).unwrap_or(false))
+        .map(|(id, _)| id))
+}
+
+/// The trimmed username and bcrypt hash from ADMIN_USERNAME and ADMIN_PASSWORD or
+/// ADMIN_PASSWORD_HASH, or None if they aren't set.
+fn configured_admin() -> std::result::Result<Option<(String, String)>, String> {
+    let username = std::env::var("ADMIN_USERNAME").ok().filter(|u| !u.trim().is_empty());
+    let password = std::env::var("ADMIN_PASSWORD").ok().filter(|p| !p.is_empty());
+    let password_hash = std::env::var("ADMIN_PASSWORD_HASH").ok().filter(|h| !h.is_empty());
+
+    let password_hash = match (username.is_some(), password, password_hash) {
+        (true, Some(_), Some(_)) => {
+            return Err("Set only one of ADMIN_PASSWORD and ADMIN_PASSWORD_HASH".to_string());
+        }
+        (true, Some(password), None) => {
+            crate::password_policy::validate(&password)
+                .map_err(|_| "ADMIN_PASSWORD is too short or too common".to_string())?;
+            crate::password_policy::hash(&password).map_err(|e| e.to_string())?
+        }
+        (true, None, Some(hash)) => {
+            // Anything bcrypt can't parse would leave an account nobody can log in to
+            bcrypt::verify("", &hash).map_err(|_| "ADMIN_PASSWORD_HASH is not a bcrypt hash".to_string())?;
+            // Swapping the default password for the same password would change nothing
+            if bcrypt::verify(LEGACY_ADMIN_PASSWORD, &hash).unwrap_or(false) {
+                return Err("ADMIN_PASSWORD_HASH is a hash of the old default password".to_string());
+            }
+            hash
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(username.map(|username| (username.trim().to_string(), password_hash)))
+}
+
+/// Adds a column to an existing table, returning whether it had to be created.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|c| c.ok())
+        .any(|name| name == column);
+
+    if exists {
+        return Ok(false);
+    }
+
+    conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    Ok(true)
+}
@@ -0,0 +1,215 @@
+use minijinja::{Environment, UndefinedBehavior};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+// Outgoing mail is rendered from MiniJinja templates, each with a subject line, a plain
+// text body and an HTML body. The defaults live in src/email_templates/; an owner can
+// override a template through the admin API, and the override is kept in the database.
+// Only the HTML body is autoescaped. Referencing a variable the template doesn't get is
+// an error, so typos are caught when a template is saved rather than in a sent email.
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Parts {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+#[derive(Serialize)]
+pub struct Rendered {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+pub struct Definition {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub variables: &'static [&'static str],
+    subject: &'static str,
+    text: &'static str,
+    html: &'static str,
+    sample: fn() -> Value,
+}
+
+pub const DEFINITIONS: &[Definition] = &[
+    Definition {
+        name: "contact_notification",
+        description: "Sent to CONTACT_EMAIL for every contact form message that isn't spam",
+        variables: &["name", "email", "subject", "message"],
+        subject: include_str!("email_templates/contact_notification.subject"),
+        text: include_str!("email_templates/contact_notification.txt"),
+        html: include_str!("email_templates/contact_notification.html"),
+        sample: sample_contact,
+    },
+    Definition {
+        name: "contact_autoreply",
+        description: "Automatic acknowledgement sent to the person who used the contact form",
+        variables: &["name", "email", "subject"],
+        subject: include_str!("email_templates/contact_autoreply.subject"),
+        text: include_str!("email_templates/contact_autoreply.txt"),
+        html: include_str!("email_templates/contact_autoreply.html"),
+        sample: || json!({
+            "name": "Jane Doe",
+            "email": "jane@example.com",
+            "subject": "Project inquiry",
+        }),
+    },
+    Definition {
+        name: "contact_reply",
+        description: "An admin's reply to a contact form message, sent to the person who wrote it",
+        variables: &["name", "subject", "message"],
+        subject: include_str!("email_templates/contact_reply.subject"),
+        text: include_str!("email_templates/contact_reply.txt"),
+        html: include_str!("email_templates/contact_reply.html"),
+        sample: || json!({
+            "name": "Jane Doe",
+            "subject": "Re: Project inquiry",
+            "message": "Hi Jane,\n\nThanks for reaching out! I'd be happy to help. Are you free for a call this week?\n\nJosh",
+        }),
+    },
+    Definition {
+        name: "contact_digest",
+        description: "Summary of contact form messages that came in over a period, sent to CONTACT_EMAIL",
+        variables: &["count", "contacts", "link"],
+        subject: include_str!("email_templates/contact_digest.subject"),
+        text: include_str!("email_templates/contact_digest.txt"),
+        html: include_str!("email_templates/contact_digest.html"),
+        sample: || json!({
+            "count": 2,
+            "contacts": [
+                { "name": "Jane Doe", "email": "jane@example.com", "subject": "Project inquiry", "created_at": "2024-03-01 09:30:00" },
+                { "name": "Sam Lee", "email": "sam@example.org", "subject": "Speaking at our meetup", "created_at": "2024-03-01 16:05:00" },
+            ],
+            "link": "https://example.com/admin/dashboard",
+        }),
+    },
+    Definition {
+        name: "password_reset",
+        description: "Password reset link for an admin account",
+        variables: &["username", "link", "expires_minutes"],
+        subject: include_str!("email_templates/password_reset.subject"),
+        text: include_str!("email_templates/password_reset.txt"),
+        html: include_str!("email_templates/password_reset.html"),
+        sample: || json!({
+            "username": "josh",
+            "link": "https://example.com/admin/reset-password?token=sample-token",
+            "expires_minutes": 30,
+        }),
+    },
+];
+
+fn sample_contact() -> Value {
+    json!({
+        "name": "Jane Doe",
+        "email": "jane@example.com",
+        "subject": "Project inquiry",
+        "message": "Hi Josh,\n\nI'd like to talk about building a website for my bakery.\n\nThanks,\nJane",
+    })
+}
+
+pub fn definition(name: &str) -> Option<&'static Definition> {
+    DEFINITIONS.iter().find(|definition| definition.name == name)
+}
+
+impl Definition {
+    pub fn defaults(&self) -> Parts {
+        Parts {
+            subject: self.subject.to_string(),
+            text: self.text.to_string(),
+            html: self.html.to_string(),
+        }
+    }
+
+    /// Example variables for previews and for checking a template before it's saved.
+    pub fn sample_data(&self) -> Value {
+        (self.sample)()
+    }
+}
+
+/// The stored override for `name`, if an admin has saved one.
+pub fn stored(conn: &Connection, name: &str) -> Result<Option<Parts>> {
+    conn.query_row(
+        "SELECT subject, text_body, html_body FROM email_templates WHERE name = ?1",
+        [name],
+        |row| Ok(Parts { subject: row.get(0)?, text: row.get(1)?, html: row.get(2)? }),
+    ).optional()
+}
+
+pub fn save(conn: &Connection, name: &str, parts: &Parts, updated_by: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO email_templates (name, subject, text_body, html_body, updated_by, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP)
+         ON CONFLICT(name) DO UPDATE SET subject = ?2, text_body = ?3, html_body = ?4,
+             updated_by = ?5, updated_at = CURRENT_TIMESTAMP",
+        params![name, parts.subject, parts.text, parts.html, updated_by],
+    )?;
+    Ok(())
+}
+
+/// Drops the override so the default applies again. Returns whether there was one.
+pub fn reset(conn: &Connection, name: &str) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM email_templates WHERE name = ?1", [name])? > 0)
+}
+
+/// Renders each part of a template against `data`. The error says which part failed and why.
+pub fn render_parts(name: &str, parts: &Parts, data: &Value) -> std::result::Result<Rendered, String> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+
+    // The file extension in each name is what turns autoescaping on for HTML only
+    let render = |part: &str, extension: &str, source: &str| {
+        env.render_named_str(&format!("{}.{}", name, extension), source, data)
+            .map_err(|e| format!("{}: {}", part, e))
+    };
+
+    let subject = render("subject", "subject", &parts.subject)?;
+
+    Ok(Rendered {
+        // A subject is one line, whatever the template produced
+        subject: subject.split_whitespace().collect::<Vec<_>>().join(" "),
+        text: render("text", "txt", &parts.text)?,
+        html: render("html", "html", &parts.html)?,
+    })
+}
+
+/// Renders the named template for sending. If a stored override fails to render,
+/// the default is used instead so the email still goes out.
+pub fn render(conn: &Connection, name: &str, data: &Value) -> std::result::Result<Rendered, String> {
+    let definition = definition(name).ok_or_else(|| format!("Unknown email template '{}'", name))?;
+    let stored = stored(conn, name).map_err(|e| e.to_string())?;
+
+    if let Some(parts) = stored {
+        match render_parts(name, &parts, data) {
+            Ok(rendered) => return Ok(rendered),
+            Err(e) => tracing::error!("Email template '{}' failed to render, using the default: {}", name, e),
+        }
+    }
+
+    render_parts(name, &definition.defaults(), data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_render_against_their_sample_data() {
+        for definition in DEFINITIONS {
+            if let Err(e) = render_parts(definition.name, &definition.defaults(), &definition.sample_data()) {
+                panic!("{}: {}", definition.name, e);
+            }
+        }
+    }
+
+    #[test]
+    fn digest_lists_every_contact() {
+        let definition = definition("contact_digest").unwrap();
+        let rendered = render_parts(definition.name, &definition.defaults(), &definition.sample_data()).unwrap();
+
+        assert_eq!(rendered.subject, "2 new contact messages");
+        assert!(rendered.text.contains("- Project inquiry\n  From Jane Doe <jane@example.com>"));
+        assert!(rendered.html.contains("Sam Lee &lt;sam@example.org&gt;"));
+    }
+}
@@ -0,0 +1,296 @@
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use rusqlite::Row;
+use serde::Serialize;
+use crate::api_handlers::contact;
+
+// Contact export formats. Each submission becomes one self-contained entry, so an
+// export can be written out a batch of rows at a time.
+
+/// The columns `Entry::from_row` reads, in order.
+pub const COLUMNS: &str = "id, created_at, name, email, subject, message, status, read, archived_at,
+    spam_score, spam_probability, spam_label, message_id";
+
+const CSV_HEADER: &str = "id,created_at,name,email,subject,message,status,read,archived_at,spam_score,spam_probability,spam_label\r\n";
+
+#[derive(Serialize)]
+pub struct Entry {
+    id: i64,
+    created_at: String,
+    name: String,
+    email: String,
+    subject: String,
+    message: String,
+    status: String,
+    read: bool,
+    archived_at: Option<String>,
+    spam_score: f64,
+    spam_probability: Option<f64>,
+    spam_label: Option<String>,
+    message_id: Option<String>,
+}
+
+impl Entry {
+    pub fn from_row(row: &Row) -> rusqlite::Result<Entry> {
+        Ok(Entry {
+            id: row.get(0)?,
+            created_at: row.get(1)?,
+            name: row.get(2)?,
+            email: row.get(3)?,
+            subject: row.get(4)?,
+            message: row.get(5)?,
+            status: row.get(6)?,
+            read: row.get::<_, i64>(7)? != 0,
+            archived_at: row.get(8)?,
+            spam_score: row.get(9)?,
+            spam_probability: row.get(10)?,
+            spam_label: row.get(11)?,
+            message_id: row.get(12)?,
+        })
+    }
+
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Csv,
+    JsonLines,
+    Mbox,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> Option<Format> {
+        match value {
+            "csv" => Some(Format::Csv),
+            "jsonl" => Some(Format::JsonLines),
+            "mbox" => Some(Format::Mbox),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::JsonLines => "application/x-ndjson",
+            Format::Mbox => "application/mbox",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::JsonLines => "jsonl",
+            Format::Mbox => "mbox",
+        }
+    }
+
+    /// What goes before the first entry.
+    pub fn header(&self) -> &'static str {
+        match self {
+            Format::Csv => CSV_HEADER,
+            Format::JsonLines | Format::Mbox => "",
+        }
+    }
+
+    pub fn write(&self, entry: &Entry, out: &mut String) {
+        match self {
+            Format::Csv => write_csv(entry, out),
+            Format::JsonLines => {
+                out.push_str(&serde_json::to_string(entry).unwrap_or_default());
+                out.push('\n');
+            }
+            Format::Mbox => write_mbox(entry, out),
+        }
+    }
+}
+
+/// Quotes a CSV field when needed. Text a spreadsheet would read as a formula gets a
+/// leading apostrophe, since every field here came from the public contact form.
+fn csv_field(value: &str, out: &mut String) {
+    let formula = value.starts_with(['=', '+', '-', '@', '\t', '\r']);
+    let quote = formula || value.contains([',', '"', '\n', '\r']);
+
+    if quote {
+        out.push('"');
+    }
+    if formula {
+        out.push('\'');
+    }
+    out.push_str(&value.replace('"', "\"\""));
+    if quote {
+        out.push('"');
+    }
+}
+
+fn write_csv(entry: &Entry, out: &mut String) {
+    let fields = [
+        entry.id.to_string(),
+        entry.created_at.clone(),
+        entry.name.clone(),
+        entry.email.clone(),
+        entry.subject.clone(),
+        entry.message.clone(),
+        entry.status.clone(),
+        entry.read.to_string(),
+        entry.archived_at.clone().unwrap_or_default(),
+        entry.spam_score.to_string(),
+        entry.spam_probability.map(|p| p.to_string()).unwrap_or_default(),
+        entry.spam_label.clone().unwrap_or_default(),
+    ];
+
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        csv_field(field, out);
+    }
+    out.push_str("\r\n");
+}
+
+/// Control characters, line breaks included, as spaces.
+fn single_line(value: &str) -> String {
+    value.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
+
+/// One mboxrd entry: a `From ` separator line, the submission as an RFC 5322 message
+/// from the submitter to CONTACT_EMAIL, and a blank line. Body lines that start with
+/// `From ` (after any `>`s) get another `>` so readers don't split the message there.
+fn write_mbox(entry: &Entry, out: &mut String) {
+    let created_at = NaiveDateTime::parse_from_str(&entry.created_at, "%Y-%m-%d %H:%M:%S")
+        .map(|naive| Utc.from_utc_datetime(&naive))
+        .unwrap_or_else(|_| Utc::now());
+
+    let to: Mailbox = contact::contact_email().parse()
+        .unwrap_or_else(|_| Mailbox::new(None, "contact@localhost".parse().expect("valid address")));
+    // Anything stored before submissions were validated may not parse, or may hold line
+    // breaks that lettre refuses to fold into a header; keep it readable
+    let name = single_line(&entry.name);
+    let from = entry.email.parse()
+        .map(|address| Mailbox::new(Some(name.clone()), address))
+        .unwrap_or_else(|_| Mailbox::new(Some(format!("{} ({})", name, single_line(&entry.email))), to.email.clone()));
+
+    let mut builder = lettre::Message::builder()
+        .from(from)
+        .to(to)
+        .subject(single_line(&entry.subject))
+        .date(created_at.into());
+    if let Some(message_id) = &entry.message_id {
+        builder = builder.message_id(Some(message_id.clone()));
+    }
+
+    let message = match builder.header(ContentType::TEXT_PLAIN).body(entry.message.clone()) {
+        Ok(message) => String::from_utf8_lossy(&message.formatted()).replace("\r\n", "\n"),
+        Err(e) => {
+            tracing::error!("Couldn't export contact {} as mbox: {}", entry.id, e);
+            return;
+        }
+    };
+
+    let sender = if entry.email.is_empty() || entry.email.contains(char::is_whitespace) {
+        "MAILER-DAEMON"
+    } else {
+        &entry.email
+    };
+    out.push_str(&format!("From {} {}\n", sender, created_at.format("%a %b %e %H:%M:%S %Y")));
+
+    for line in message.lines() {
+        if line.trim_start_matches('>').starts_with("From ") {
+            out.push('>');
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, email: &str, subject: &str, message: &str) -> Entry {
+        Entry {
+            id: 1,
+            created_at: "2024-03-01 12:00:00".to_string(),
+            name: name.to_string(),
+            email: email.to_string(),
+            subject: subject.to_string(),
+            message: message.to_string(),
+            status: "inbox".to_string(),
+            read: false,
+            archived_at: None,
+            spam_score: 0.0,
+            spam_probability: None,
+            spam_label: None,
+            message_id: None,
+        }
+    }
+
+    fn csv(value: &str) -> String {
+        let mut out = String::new();
+        csv_field(value, &mut out);
+        out
+    }
+
+    fn mbox(entry: &Entry) -> String {
+        let mut out = String::new();
+        Format::Mbox.write(entry, &mut out);
+        out
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv("Jane"), "Jane");
+        assert_eq!(csv("Doe, Jane"), "\"Doe, Jane\"");
+        assert_eq!(csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv("line one\nline two"), "\"line one\nline two\"");
+    }
+
+    #[test]
+    fn csv_formulas_are_defused() {
+        for formula in ["=HYPERLINK(\"http://evil\")", "+1+1", "-2+3", "@SUM(A1)", "\tcmd", "\rcmd"] {
+            let field = csv(formula);
+            assert!(field.starts_with("\"'"), "{:?} became {:?}", formula, field);
+        }
+        assert_eq!(csv("=1+1"), "\"'=1+1\"");
+    }
+
+    #[test]
+    fn csv_row_stays_on_one_record() {
+        let mut out = String::new();
+        Format::Csv.write(&entry("Jane", "jane@example.com", "Hi, there", "a\r\nb"), &mut out);
+
+        assert!(out.ends_with("\r\n"));
+        assert!(out.contains(",\"Hi, there\",\"a\r\nb\","));
+        assert!(out.starts_with("1,2024-03-01 12:00:00,Jane,jane@example.com,"));
+    }
+
+    #[test]
+    fn mbox_body_lines_cannot_start_a_new_message() {
+        let out = mbox(&entry("Jane", "jane@example.com", "Hello", "From the top\n>From quoted\nFromage is fine"));
+
+        let separators: Vec<&str> = out.lines().filter(|line| line.starts_with("From ")).collect();
+        assert_eq!(separators, ["From jane@example.com Fri Mar  1 12:00:00 2024"]);
+        assert!(out.contains("\n>From the top\n"));
+        assert!(out.contains("\n>>From quoted\n"));
+        assert!(out.contains("\nFromage is fine\n"));
+        assert!(out.ends_with("\n\n"));
+    }
+
+    #[test]
+    fn mbox_headers_cannot_be_injected() {
+        let out = mbox(&entry("Jane\nBcc: victim@example.com", "jane@example.com", "Hi\r\nFrom evil@example.com Mon Jan  1", "Body"));
+
+        assert!(!out.lines().any(|line| line.starts_with("Bcc:")));
+        assert_eq!(out.lines().filter(|line| line.starts_with("From ")).count(), 1);
+    }
+
+    #[test]
+    fn mbox_separator_falls_back_for_unusable_senders() {
+        let out = mbox(&entry("Jane", "jane at example com", "Hello", "Body"));
+        assert!(out.starts_with("From MAILER-DAEMON "));
+    }
+}
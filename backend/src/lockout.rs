@@ -0,0 +1,204 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+// Failures allowed before any delay kicks in
+const FREE_ATTEMPTS: i64 = 3;
+// After this many failures the key is locked out for LOCKOUT_SECS
+const LOCKOUT_THRESHOLD: i64 = 10;
+const LOCKOUT_SECS: i64 = 15 * 60;
+// Failures older than this no longer count towards the backoff
+const FAILURE_WINDOW_SECS: i64 = 60 * 60;
+
+pub fn username_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+pub fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+/// Keys for throttling password reset requests, per account and per client. Kept apart
+/// from the login keys so asking for reset links never locks anyone out of signing in.
+pub fn password_reset_keys(username: &str, ip: &str) -> [String; 2] {
+    [format!("reset-user:{}", username.to_lowercase()), format!("reset-ip:{}", ip)]
+}
+
+/// Key for throttling contact form acknowledgements per client. Each one sent counts as
+/// an attempt, so one client can't use the form to mail a list of addresses.
+pub fn autoreply_key(ip: &str) -> String {
+    format!("autoreply-ip:{}", ip)
+}
+
+/// Returns how many seconds the caller has to wait before the next attempt
+/// on any of `keys`, or `None` if it may try now.
+pub fn retry_after(conn: &Connection, keys: &[String]) -> Result<Option<i64>> {
+    let now = chrono::Utc::now().timestamp();
+    let mut wait = 0;
+
+    for key in keys {
+        let locked_until: Option<i64> = conn.query_row(
+            "SELECT locked_until FROM login_attempts WHERE key = ?1",
+            [key],
+            |row| row.get(0),
+        ).optional()?;
+
+        if let Some(locked_until) = locked_until {
+            wait = wait.max(locked_until - now);
+        }
+    }
+
+    Ok(if wait > 0 { Some(wait) } else { None })
+}
+
+/// Records a failed attempt against every key, doubling the delay with each
+/// failure past FREE_ATTEMPTS and locking the key once LOCKOUT_THRESHOLD is hit.
+pub fn record_failure(conn: &Connection, keys: &[String]) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+
+    for key in keys {
+        let previous: Option<(i64, i64)> = conn.query_row(
+            "SELECT failures, last_failure_at FROM login_attempts WHERE key = ?1",
+            [key],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+
+        let failures = match previous {
+            Some((failures, last_failure_at)) if now - last_failure_at < FAILURE_WINDOW_SECS => failures + 1,
+            _ => 1,
+        };
+
+        let delay = if failures >= LOCKOUT_THRESHOLD {
+            LOCKOUT_SECS
+        } else if failures > FREE_ATTEMPTS {
+            (1i64 << (failures - FREE_ATTEMPTS)).min(LOCKOUT_SECS)
+        } else {
+            0
+        };
+
+        conn.execute(
+            "INSERT INTO login_attempts (key, failures, last_failure_at, locked_until)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(key) DO UPDATE SET
+                failures = excluded.failures,
+                last_failure_at = excluded.last_failure_at,
+                locked_until = excluded.locked_until",
+            params![key, failures, now, now + delay],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Clears the failure history for every key after a successful login.
+pub fn reset(conn: &Connection, keys: &[String]) -> Result<()> {
+    for key in keys {
+        conn.execute("DELETE FROM login_attempts WHERE key = ?1", [key])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::AppState;
+
+    fn db() -> Connection {
+        AppState::migrate(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn fail(conn: &Connection, keys: &[String], times: i64) {
+        for _ in 0..times {
+            record_failure(conn, keys).unwrap();
+        }
+    }
+
+    #[test]
+    fn first_failures_are_free() {
+        let conn = db();
+        let keys = [username_key("jane")];
+
+        fail(&conn, &keys, FREE_ATTEMPTS);
+        assert_eq!(retry_after(&conn, &keys).unwrap(), None);
+    }
+
+    #[test]
+    fn delay_doubles_after_free_attempts() {
+        let conn = db();
+        let keys = [username_key("jane")];
+
+        fail(&conn, &keys, FREE_ATTEMPTS + 1);
+        let first = retry_after(&conn, &keys).unwrap().expect("delayed after the free attempts");
+        assert!((1..=2).contains(&first), "waited {}", first);
+
+        fail(&conn, &keys, 2);
+        let third = retry_after(&conn, &keys).unwrap().expect("still delayed");
+        assert!((7..=8).contains(&third), "waited {}", third);
+    }
+
+    #[test]
+    fn threshold_locks_the_key_out() {
+        let conn = db();
+        let keys = [username_key("jane")];
+
+        fail(&conn, &keys, LOCKOUT_THRESHOLD);
+        let wait = retry_after(&conn, &keys).unwrap().expect("locked out");
+        assert!(wait > LOCKOUT_SECS - 5, "waited {}", wait);
+    }
+
+    #[test]
+    fn any_locked_key_blocks_the_attempt() {
+        let conn = db();
+        let locked = [username_key("jane"), ip_key("203.0.113.7")];
+
+        fail(&conn, &locked[1..], LOCKOUT_THRESHOLD);
+        assert!(retry_after(&conn, &locked).unwrap().is_some());
+        // Usernames are matched case-insensitively
+        assert!(retry_after(&conn, &[username_key("JANE"), ip_key("203.0.113.7")]).unwrap().is_some());
+        assert_eq!(retry_after(&conn, &[username_key("jane"), ip_key("198.51.100.1")]).unwrap(), None);
+    }
+
+    #[test]
+    fn reset_clears_the_history() {
+        let conn = db();
+        let keys = [username_key("jane")];
+
+        fail(&conn, &keys, LOCKOUT_THRESHOLD);
+        reset(&conn, &keys).unwrap();
+        fail(&conn, &keys, FREE_ATTEMPTS);
+        assert_eq!(retry_after(&conn, &keys).unwrap(), None);
+    }
+
+    #[test]
+    fn old_failures_stop_counting() {
+        let conn = db();
+        let keys = [username_key("jane")];
+
+        fail(&conn, &keys, LOCKOUT_THRESHOLD - 1);
+        conn.execute(
+            "UPDATE login_attempts SET last_failure_at = last_failure_at - ?1, locked_until = 0",
+            [FAILURE_WINDOW_SECS],
+        ).unwrap();
+
+        fail(&conn, &keys, 1);
+        assert_eq!(retry_after(&conn, &keys).unwrap(), None);
+    }
+
+    #[test]
+    fn password_reset_requests_are_throttled_apart_from_logins() {
+        let conn = db();
+        let reset_keys = password_reset_keys("Jane", "203.0.113.7");
+
+        fail(&conn, &reset_keys, LOCKOUT_THRESHOLD);
+        assert!(retry_after(&conn, &password_reset_keys("jane", "198.51.100.1")).unwrap().is_some());
+        assert_eq!(retry_after(&conn, &[username_key("jane"), ip_key("203.0.113.7")]).unwrap(), None);
+    }
+
+    #[test]
+    fn autoreplies_are_throttled_apart_from_logins() {
+        let conn = db();
+        let keys = [autoreply_key("203.0.113.7")];
+
+        fail(&conn, &keys, FREE_ATTEMPTS + 1);
+        assert!(retry_after(&conn, &keys).unwrap().is_some());
+        assert_eq!(retry_after(&conn, &[ip_key("203.0.113.7")]).unwrap(), None);
+    }
+}
@@ -0,0 +1,249 @@
+use async_trait::async_trait;
+use lettre::message::header::{ContentType, HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// Outgoing email and the transports that deliver it. MAIL_TRANSPORT picks one:
+// 'mailgun' (the default) posts to the Mailgun HTTP API, 'smtp' relays through an SMTP
+// server over STARTTLS, and 'file' writes each message to a directory as an .eml file
+// for local development and tests.
+
+const DEFAULT_MAILGUN_BASE_URL: &str = "https://api.mailgun.net";
+// Submission port; STARTTLS upgrades the connection before credentials are sent
+const DEFAULT_SMTP_PORT: u16 = 587;
+const DEFAULT_MAIL_FILE_DIR: &str = "mail";
+// The outbox sends one message at a time, so a server that never answers mustn't hold it up for long
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Email {
+    pub from_name: String,
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    // Sent as an alternative to `text` for clients that show HTML
+    pub html: Option<String>,
+    // Extra MIME headers (threading, Reply-To), passed through as-is
+    pub headers: Vec<(String, String)>,
+}
+
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), String>;
+}
+
+/// The transport MAIL_TRANSPORT selects, configured from the environment.
+pub fn transport() -> Result<Box<dyn Transport>, String> {
+    match std::env::var("MAIL_TRANSPORT").as_deref().unwrap_or("mailgun") {
+        "mailgun" => Ok(Box::new(Mailgun::from_env()?)),
+        "smtp" => Ok(Box::new(Smtp::from_env()?)),
+        "file" => Ok(Box::new(FileSink::from_env())),
+        other => Err(format!("Unknown MAIL_TRANSPORT '{}'", other)),
+    }
+}
+
+/// Sends `email` through the configured transport.
+pub async fn send(email: &Email) -> Result<(), String> {
+    transport()?.send(email).await
+}
+
+/// The address mail is sent from: MAIL_FROM_ADDRESS, else `mailgun@<MAILGUN_DOMAIN>`,
+/// else a no-reply address on PUBLIC_HOST.
+fn from_address() -> String {
+    if let Ok(address) = std::env::var("MAIL_FROM_ADDRESS") {
+        return address;
+    }
+    if let Ok(domain) = std::env::var("MAILGUN_DOMAIN") {
+        return format!("mailgun@{}", domain);
+    }
+
+    let host = std::env::var("PUBLIC_HOST").unwrap_or_else(|_| "localhost".to_string());
+    format!("noreply@{}", host.split(':').next().unwrap_or("localhost"))
+}
+
+/// A fresh RFC 5322 Message-ID on the sending domain, angle brackets included.
+pub fn new_message_id() -> String {
+    let from = from_address();
+    let domain = from.rsplit_once('@').map(|(_, domain)| domain).unwrap_or("localhost");
+
+    format!("<{:032x}@{}>", rand::random::<u128>(), domain)
+}
+
+/// Builds the MIME message the SMTP and file transports send.
+fn build_message(email: &Email) -> Result<lettre::Message, String> {
+    let from = Mailbox::new(
+        Some(email.from_name.clone()),
+        from_address().parse().map_err(|e| format!("Invalid from address: {}", e))?,
+    );
+    let to: Mailbox = email.to.parse().map_err(|e| format!("Invalid recipient: {}", e))?;
+
+    let mut builder = lettre::Message::builder()
+        .from(from)
+        .to(to)
+        .subject(&email.subject);
+
+    if !email.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("Message-Id")) {
+        builder = builder.message_id(None);
+    }
+    for (name, value) in &email.headers {
+        let name = HeaderName::new_from_ascii(name.clone()).map_err(|_| format!("Invalid header name: {}", name))?;
+        builder = builder.raw_header(HeaderValue::new(name, value.clone()));
+    }
+
+    let message = match &email.html {
+        Some(html) => builder.multipart(MultiPart::alternative_plain_html(email.text.clone(), html.clone())),
+        None => builder.header(ContentType::TEXT_PLAIN).body(email.text.clone()),
+    };
+    message.map_err(|e| format!("Failed to build email: {}", e))
+}
+
+/// Mailgun's HTTP API. MAILGUN_BASE_URL points it at the EU region or a local stub.
+pub struct Mailgun {
+    base_url: String,
+    domain: String,
+    api_key: String,
+}
+
+impl Mailgun {
+    fn from_env() -> Result<Self, String> {
+        Ok(Mailgun {
+            base_url: std::env::var("MAILGUN_BASE_URL").unwrap_or_else(|_| DEFAULT_MAILGUN_BASE_URL.to_string()),
+            domain: std::env::var("MAILGUN_DOMAIN").map_err(|_| "MAILGUN_DOMAIN not set")?,
+            api_key: std::env::var("MAILGUN_API_KEY").map_err(|_| "MAILGUN_API_KEY not set")?,
+        })
+    }
+}
+
+/// One HTTP client for every Mailgun request, so connections are reused.
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .build()
+            .expect("HTTP client configuration is valid")
+    })
+}
+
+#[async_trait]
+impl Transport for Mailgun {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        tracing::info!("Sending email via Mailgun: domain={}, to={}", self.domain, email.to);
+
+        let url = format!("{}/v3/{}/messages", self.base_url.trim_end_matches('/'), self.domain);
+
+        let mut form_params = vec![
+            ("from".to_string(), format!("{} <{}>", email.from_name, from_address())),
+            ("to".to_string(), email.to.clone()),
+            ("subject".to_string(), email.subject.clone()),
+            ("text".to_string(), email.text.clone()),
+        ];
+        if let Some(html) = &email.html {
+            form_params.push(("html".to_string(), html.clone()));
+        }
+        for (name, value) in &email.headers {
+            form_params.push((format!("h:{}", name), value.clone()));
+        }
+
+        let response = http_client()
+            .post(&url)
+            .basic_auth("api", Some(&self.api_key))
+            .form(&form_params)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send email: {}", e))?;
+
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        tracing::info!("Mailgun response: status={}, body={}", status, body);
+
+        if !status.is_success() {
+            return Err(format!("Mailgun error: {} - {}", status, body));
+        }
+
+        Ok(())
+    }
+}
+
+/// An SMTP relay at SMTP_HOST:SMTP_PORT. STARTTLS is required, so mail and the optional
+/// SMTP_USERNAME/SMTP_PASSWORD login never cross the network in the clear.
+pub struct Smtp {
+    host: String,
+    port: u16,
+    credentials: Option<Credentials>,
+}
+
+impl Smtp {
+    fn from_env() -> Result<Self, String> {
+        let credentials = match (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+            (Ok(username), Ok(password)) => Some(Credentials::new(username, password)),
+            _ => None,
+        };
+
+        Ok(Smtp {
+            host: std::env::var("SMTP_HOST").map_err(|_| "SMTP_HOST not set")?,
+            port: std::env::var("SMTP_PORT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_SMTP_PORT),
+            credentials,
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for Smtp {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        tracing::info!("Sending email via SMTP: host={}, to={}", self.host, email.to);
+
+        let message = build_message(email)?;
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)
+            .map_err(|e| format!("Invalid SMTP relay: {}", e))?
+            .port(self.port)
+            .timeout(Some(SEND_TIMEOUT));
+        if let Some(credentials) = &self.credentials {
+            builder = builder.credentials(credentials.clone());
+        }
+
+        builder
+            .build()
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("SMTP error: {}", e))
+    }
+}
+
+/// Writes each message to MAIL_FILE_DIR (default ./mail) as `<uuid>.eml` instead of sending it.
+pub struct FileSink {
+    dir: String,
+}
+
+impl FileSink {
+    fn from_env() -> Self {
+        FileSink {
+            dir: std::env::var("MAIL_FILE_DIR").unwrap_or_else(|_| DEFAULT_MAIL_FILE_DIR.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for FileSink {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        let message = build_message(email)?;
+
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir, e))?;
+
+        let id = AsyncFileTransport::<Tokio1Executor>::new(&self.dir)
+            .send(message)
+            .await
+            .map_err(|e| format!("Failed to write email: {}", e))?;
+
+        tracing::info!("Wrote email to {} as {}.eml", self.dir, id);
+        Ok(())
+    }
+}
@@ -1,5 +1,23 @@
 mod api_handlers;
+mod api_keys;
+mod audit;
+mod auth;
+mod bayes;
+mod cookies;
 mod db;
+mod email_templates;
+mod export;
+mod lockout;
+mod mail;
+mod outbox;
+mod password_policy;
+mod password_reset;
+mod queue;
+mod sessions;
+mod spam;
+mod tokens;
+mod totp;
+mod webhooks;
 
 use crate::db::AppState;
 use axum::{
@@ -7,8 +25,7 @@ use axum::{
     routing::get,
     RequestPartsExt, Router,
 };
-use dotenv::dotenv;
-use std::{path::PathBuf, sync::Arc};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
 use tower::util::ServiceExt;
 use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
@@ -27,6 +44,12 @@ async fn main() {
     let api_host = std::env::var("PUBLIC_HOST").unwrap_or_else(|_| "localhost:3000".to_string());
 
     let app_state = Arc::new(AppState::new().expect("Failed to initialize database"));
+    if let Err(e) = app_state.bootstrap_admin() {
+        tracing::error!("Refusing to start: {}", e);
+        std::process::exit(1);
+    }
+    tokio::spawn(outbox::run(app_state.clone()));
+    tokio::spawn(webhooks::run(app_state.clone()));
 
     // Serve static files from the dist folder (where Astro builds to)
     // Use /app/dist for production (Fly.io), ../dist for local dev (relative to backend/)
@@ -47,6 +70,15 @@ async fn main() {
     let app = Router::new()
         // API routes
         .nest("/api/contact", api_handlers::contact::router(app_state.clone()))
+        .nest("/api/admin/users", api_handlers::users::router(app_state.clone()))
+        .nest("/api/admin/2fa", api_handlers::two_factor::router(app_state.clone()))
+        .nest("/api/admin/sessions", api_handlers::sessions::router(app_state.clone()))
+        .nest("/api/admin/api-keys", api_handlers::api_keys::router(app_state.clone()))
+        .nest("/api/admin/contacts", api_handlers::contacts::router(app_state.clone()))
+        .nest("/api/admin/email-templates", api_handlers::email_templates::router(app_state.clone()))
+        .nest("/api/admin/outbox", api_handlers::outbox::router(app_state.clone()))
+        .nest("/api/admin/webhooks", api_handlers::webhooks::router(app_state.clone()))
+        .nest("/api/admin/audit", api_handlers::audit::router(app_state.clone()))
         .nest("/api/admin", api_handlers::admin::router(app_state.clone()))
         .nest("/api/projects", api_handlers::projects::router(app_state.clone()))
         .nest("/api/knowledge", api_handlers::knowledge::router(app_state.clone()))
@@ -72,5 +104,5 @@ async fn main() {
 
     let listener = tokio::net::TcpListener::bind(api_host.clone()).await.unwrap();
     println!("🚀 Portfolio server running on http://{}", api_host);
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::sync::Arc;
+use crate::db::AppState;
+use crate::mail::{self, Email};
+use crate::queue::{self, Queue, Retry, RetryPolicy};
+
+// Durable queue for outgoing email. A message is written in the same transaction as
+// whatever it announces and one background worker delivers it, so a provider outage or
+// a machine stop only delays it. Failed sends back off exponentially; after the last
+// attempt the message is parked as 'failed' until an admin retries it.
+
+const RETRY: RetryPolicy = RetryPolicy::new("OUTBOX_MAX_ATTEMPTS", "OUTBOX_BACKOFF_BASE_SECS");
+
+/// Queues `email` for immediate delivery. `kind` says what it is, for the admin listing.
+pub fn enqueue(conn: &Connection, kind: &str, email: &Email) -> Result<i64> {
+    let headers = serde_json::to_string(&email.headers).unwrap_or_else(|_| "[]".to_string());
+
+    conn.execute(
+        "INSERT INTO email_outbox (kind, from_name, recipient, subject, body, html_body, headers, next_attempt_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![kind, email.from_name, email.to, email.subject, email.text, email.html, headers, chrono::Utc::now().timestamp()],
+    )?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+/// The pending message that has been due the longest.
+fn next_due(conn: &Connection) -> Result<Option<(i64, Email)>> {
+    conn.query_row(
+        "SELECT id, from_name, recipient, subject, body, html_body, headers FROM email_outbox
+         WHERE status = 'pending' AND next_attempt_at <= ?1
+         ORDER BY next_attempt_at, id LIMIT 1",
+        [chrono::Utc::now().timestamp()],
+        |row| {
+            let headers: String = row.get(6)?;
+            Ok((row.get(0)?, Email {
+                from_name: row.get(1)?,
+                to: row.get(2)?,
+                subject: row.get(3)?,
+                text: row.get(4)?,
+                html: row.get(5)?,
+                headers: serde_json::from_str(&headers).unwrap_or_default(),
+            }))
+        },
+    ).optional()
+}
+
+/// Marks a message sent, or schedules its next attempt, or parks it as failed.
+fn record_attempt(conn: &Connection, id: i64, outcome: &std::result::Result<(), String>) -> Result<()> {
+    let error = match outcome {
+        Ok(()) => {
+            conn.execute(
+                "UPDATE email_outbox SET status = 'sent', attempts = attempts + 1, last_error = NULL,
+                 sent_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                [id],
+            )?;
+            return Ok(());
+        }
+        Err(error) => error,
+    };
+
+    let attempts: i64 = conn.query_row(
+        "SELECT attempts + 1 FROM email_outbox WHERE id = ?1",
+        [id],
+        |row| row.get(0),
+    )?;
+
+    match RETRY.after_failure(attempts) {
+        Retry::GiveUp => {
+            tracing::error!("Giving up on outbox email {} after {} attempts: {}", id, attempts, error);
+            conn.execute(
+                "UPDATE email_outbox SET status = 'failed', attempts = ?1, last_error = ?2 WHERE id = ?3",
+                params![attempts, error, id],
+            )?;
+        }
+        Retry::At(next_attempt_at) => {
+            tracing::warn!("Outbox email {} failed (attempt {}), retrying at {}: {}", id, attempts, next_attempt_at, error);
+            conn.execute(
+                "UPDATE email_outbox SET attempts = ?1, last_error = ?2, next_attempt_at = ?3 WHERE id = ?4",
+                params![attempts, error, next_attempt_at, id],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Puts an unsent message back in the queue with a fresh set of attempts.
+/// Returns false if there's no such message or it was already sent.
+pub fn retry(conn: &Connection, id: i64) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE email_outbox SET status = 'pending', attempts = 0, next_attempt_at = ?1
+         WHERE id = ?2 AND status != 'sent'",
+        params![chrono::Utc::now().timestamp(), id],
+    )?;
+    Ok(updated > 0)
+}
+
+struct Outbox;
+
+#[async_trait]
+impl Queue for Outbox {
+    type Item = (i64, Email);
+    type Outcome = std::result::Result<(), String>;
+
+    const NAME: &'static str = "Outbox";
+
+    fn next_due(&self, conn: &Connection) -> Result<Option<(i64, Email)>> {
+        next_due(conn)
+    }
+
+    async fn deliver(&self, (_, email): &(i64, Email)) -> Self::Outcome {
+        mail::send(email).await
+    }
+
+    fn record(&self, conn: &Connection, (id, _): &(i64, Email), outcome: &Self::Outcome) -> Result<()> {
+        record_attempt(conn, *id, outcome)
+    }
+}
+
+/// Delivers queued email forever.
+pub async fn run(state: Arc<AppState>) {
+    queue::run(state, Outbox).await
+}
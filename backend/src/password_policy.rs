@@ -0,0 +1,89 @@
+use axum::http::StatusCode;
+use std::sync::OnceLock;
+
+// Bundled so the check works offline; see the file header for the format
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+// Minimum length unless PASSWORD_MIN_LENGTH says otherwise
+const DEFAULT_MIN_LENGTH: usize = 12;
+
+fn min_length() -> usize {
+    std::env::var("PASSWORD_MIN_LENGTH")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MIN_LENGTH)
+}
+
+/// The bcrypt work factor for new hashes, from BCRYPT_COST (default: bcrypt's default).
+pub fn bcrypt_cost() -> u32 {
+    std::env::var("BCRYPT_COST")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|cost| (4..=31).contains(cost))
+        .unwrap_or(bcrypt::DEFAULT_COST)
+}
+
+fn is_common(password: &str) -> bool {
+    let password = password.to_lowercase();
+    COMMON_PASSWORDS
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .any(|common| common == password)
+}
+
+/// Rejects a new password with 400 if it's too short or on the common password list.
+pub fn validate(password: &str) -> Result<(), StatusCode> {
+    if password.chars().count() < min_length() || is_common(password) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+/// Hashes a password with the configured cost.
+pub fn hash(password: &str) -> bcrypt::BcryptResult<String> {
+    bcrypt::hash(password, bcrypt_cost())
+}
+
+/// A hash of a random password at the configured cost, for checking logins to accounts
+/// that don't exist as slowly as logins to ones that do.
+pub fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| hash(&format!("{:032x}", rand::random::<u128>())).unwrap_or_default())
+}
+
+/// Whether a stored hash was made with a lower cost than is now configured.
+pub fn needs_rehash(stored_hash: &str) -> bool {
+    stored_hash
+        .parse::<bcrypt::HashParts>()
+        .is_ok_and(|parts| parts.get_cost() < bcrypt_cost())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_passwords_are_rejected() {
+        assert_eq!(validate("short-pass1"), Err(StatusCode::BAD_REQUEST));
+        assert!(validate("correct-horse-battery").is_ok());
+    }
+
+    #[test]
+    fn common_passwords_are_rejected_in_any_case() {
+        assert_eq!(validate("qwertyuiop123"), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(validate("QwertyUIOP123"), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn list_comments_are_not_passwords() {
+        assert!(!is_common("# Commonly used and breached passwords, one per line, compared case-insensitively."));
+    }
+
+    #[test]
+    fn cheaper_hashes_need_rehashing() {
+        let cheap = bcrypt::hash("correct-horse-battery", 4).unwrap();
+
+        assert!(needs_rehash(&cheap));
+        assert!(!needs_rehash("not a bcrypt hash"));
+    }
+}
@@ -0,0 +1,109 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use crate::tokens;
+
+// Reset links stay valid for 30 minutes unless PASSWORD_RESET_TTL_SECS says otherwise
+const DEFAULT_RESET_TTL_SECS: i64 = 30 * 60;
+
+pub fn reset_ttl_secs() -> i64 {
+    std::env::var("PASSWORD_RESET_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_RESET_TTL_SECS)
+}
+
+/// Issues a reset token for `user_id`, invalidating any earlier unused ones.
+pub fn create(conn: &Connection, user_id: i64) -> Result<String> {
+    let token = tokens::random(32);
+    let expires_at = chrono::Utc::now().timestamp() + reset_ttl_secs();
+
+    conn.execute(
+        "UPDATE password_resets SET used_at = CURRENT_TIMESTAMP WHERE user_id = ?1 AND used_at IS NULL",
+        [user_id],
+    )?;
+    conn.execute(
+        "INSERT INTO password_resets (user_id, token_hash, expires_at) VALUES (?1, ?2, ?3)",
+        params![user_id, tokens::hash(&token), expires_at],
+    )?;
+
+    Ok(token)
+}
+
+/// Marks a valid, unexpired token as used and returns whose it was.
+pub fn consume(conn: &Connection, token: &str) -> Result<Option<i64>> {
+    let now = chrono::Utc::now().timestamp();
+
+    let reset: Option<(i64, i64)> = conn.query_row(
+        "SELECT id, user_id FROM password_resets
+         WHERE token_hash = ?1 AND used_at IS NULL AND expires_at > ?2",
+        params![tokens::hash(token), now],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
+
+    let Some((id, user_id)) = reset else {
+        return Ok(None);
+    };
+
+    conn.execute(
+        "UPDATE password_resets SET used_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        [id],
+    )?;
+
+    Ok(Some(user_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::AppState;
+
+    fn db() -> Connection {
+        let conn = AppState::migrate(Connection::open_in_memory().unwrap()).unwrap();
+        conn.execute("INSERT INTO admin_users (username, password_hash) VALUES ('jane', 'x')", []).unwrap();
+        conn
+    }
+
+    #[test]
+    fn token_names_its_user_once() {
+        let conn = db();
+        let token = create(&conn, 1).unwrap();
+
+        assert_eq!(consume(&conn, &token).unwrap(), Some(1));
+        assert_eq!(consume(&conn, &token).unwrap(), None);
+    }
+
+    #[test]
+    fn only_a_hash_of_the_token_is_stored() {
+        let conn = db();
+        let token = create(&conn, 1).unwrap();
+
+        let stored: String = conn.query_row("SELECT token_hash FROM password_resets", [], |row| row.get(0)).unwrap();
+        assert_eq!(stored, tokens::hash(&token));
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let conn = db();
+        let token = create(&conn, 1).unwrap();
+        conn.execute("UPDATE password_resets SET expires_at = ?1", [chrono::Utc::now().timestamp() - 1]).unwrap();
+
+        assert_eq!(consume(&conn, &token).unwrap(), None);
+    }
+
+    #[test]
+    fn new_token_invalidates_earlier_ones() {
+        let conn = db();
+        let first = create(&conn, 1).unwrap();
+        let second = create(&conn, 1).unwrap();
+
+        assert_eq!(consume(&conn, &first).unwrap(), None);
+        assert_eq!(consume(&conn, &second).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn unknown_tokens_are_rejected() {
+        let conn = db();
+        create(&conn, 1).unwrap();
+
+        assert_eq!(consume(&conn, "not-a-token").unwrap(), None);
+    }
+}
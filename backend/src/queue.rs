@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use rusqlite::{Connection, Result};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::db::AppState;
+
+// The machinery shared by background delivery queues (the email outbox, webhooks): a
+// retry policy with exponential backoff, and a worker loop that hands out one due item at
+// a time and records what became of it. Delivery happens without the database lock held.
+
+const DEFAULT_MAX_ATTEMPTS: i64 = 8;
+// Doubled after each further failure
+const DEFAULT_BACKOFF_BASE_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 6 * 60 * 60;
+// How often an idle worker looks for due items
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often, and how far apart, a queue retries a failed item. Both limits can be
+/// overridden through the named environment variables.
+pub struct RetryPolicy {
+    max_attempts_var: &'static str,
+    backoff_base_var: &'static str,
+}
+
+/// What to do with an item after a failed attempt.
+#[derive(Debug, PartialEq)]
+pub enum Retry {
+    /// Try again at this unix timestamp
+    At(i64),
+    /// Out of attempts; park it until an admin retries it
+    GiveUp,
+}
+
+impl RetryPolicy {
+    pub const fn new(max_attempts_var: &'static str, backoff_base_var: &'static str) -> Self {
+        RetryPolicy { max_attempts_var, backoff_base_var }
+    }
+
+    fn max_attempts(&self) -> i64 {
+        std::env::var(self.max_attempts_var)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|attempts| *attempts > 0)
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS)
+    }
+
+    fn backoff_base_secs(&self) -> i64 {
+        std::env::var(self.backoff_base_var)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_BACKOFF_BASE_SECS)
+    }
+
+    /// Seconds to wait after the `attempts`th failure: base, 2×base, 4×base, ... capped.
+    pub fn backoff_secs(&self, attempts: i64) -> i64 {
+        let doublings = (attempts - 1).clamp(0, 30) as u32;
+        self.backoff_base_secs()
+            .saturating_mul(1 << doublings)
+            .min(MAX_BACKOFF_SECS)
+    }
+
+    /// The next step for an item whose `attempts`th attempt just failed.
+    pub fn after_failure(&self, attempts: i64) -> Retry {
+        if attempts >= self.max_attempts() {
+            Retry::GiveUp
+        } else {
+            Retry::At(chrono::Utc::now().timestamp() + self.backoff_secs(attempts))
+        }
+    }
+}
+
+/// A table of work that a background worker drains.
+#[async_trait]
+pub trait Queue: Send + Sync {
+    type Item: Send + Sync;
+    type Outcome: Send + Sync;
+
+    /// Names the queue in log messages
+    const NAME: &'static str;
+
+    /// The item that has been due the longest, if any is due.
+    fn next_due(&self, conn: &Connection) -> Result<Option<Self::Item>>;
+
+    async fn deliver(&self, item: &Self::Item) -> Self::Outcome;
+
+    /// Stores the outcome of an attempt, scheduling a retry if it failed.
+    fn record(&self, conn: &Connection, item: &Self::Item, outcome: &Self::Outcome) -> Result<()>;
+}
+
+/// Delivers items from `queue` forever.
+pub async fn run<Q: Queue>(state: Arc<AppState>, queue: Q) {
+    loop {
+        let due = match state.conn.lock() {
+            Ok(conn) => queue.next_due(&conn),
+            Err(_) => {
+                tracing::error!("{} worker stopping: database lock poisoned", Q::NAME);
+                return;
+            }
+        };
+
+        let item = match due {
+            Ok(Some(item)) => item,
+            Ok(None) => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            }
+            Err(e) => {
+                tracing::error!("{} worker couldn't read the queue: {}", Q::NAME, e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            }
+        };
+
+        let outcome = queue.deliver(&item).await;
+
+        let recorded = match state.conn.lock() {
+            Ok(conn) => queue.record(&conn, &item, &outcome),
+            Err(_) => {
+                tracing::error!("{} worker stopping: database lock poisoned", Q::NAME);
+                return;
+            }
+        };
+        if let Err(e) = recorded {
+            tracing::error!("{} worker couldn't record a delivery result: {}", Q::NAME, e);
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Variables nothing sets, so the defaults apply
+    const DEFAULTS: RetryPolicy = RetryPolicy::new("QUEUE_TEST_UNSET_MAX_ATTEMPTS", "QUEUE_TEST_UNSET_BACKOFF_BASE_SECS");
+
+    #[test]
+    fn backoff_doubles_from_the_base() {
+        let delays: Vec<i64> = (1..=4).map(|attempts| DEFAULTS.backoff_secs(attempts)).collect();
+        assert_eq!(delays, [30, 60, 120, 240]);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        assert_eq!(DEFAULTS.backoff_secs(20), MAX_BACKOFF_SECS);
+        assert_eq!(DEFAULTS.backoff_secs(i64::MAX), MAX_BACKOFF_SECS);
+        assert_eq!(DEFAULTS.backoff_secs(0), DEFAULT_BACKOFF_BASE_SECS);
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        let now = chrono::Utc::now().timestamp();
+        match DEFAULTS.after_failure(DEFAULT_MAX_ATTEMPTS - 1) {
+            Retry::At(at) => assert!(at >= now + DEFAULTS.backoff_secs(DEFAULT_MAX_ATTEMPTS - 1)),
+            Retry::GiveUp => panic!("gave up an attempt early"),
+        }
+        assert_eq!(DEFAULTS.after_failure(DEFAULT_MAX_ATTEMPTS), Retry::GiveUp);
+    }
+}
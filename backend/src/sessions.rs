@@ -0,0 +1,251 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use crate::auth;
+use crate::tokens;
+
+// Refresh tokens keep a session alive for 30 days unless ADMIN_REFRESH_TTL_SECS says otherwise
+const DEFAULT_REFRESH_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
+pub struct SessionTokens {
+    pub session_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+/// Why a refresh token was turned down.
+pub enum RefreshError {
+    Invalid,
+    Database(rusqlite::Error),
+}
+
+impl From<rusqlite::Error> for RefreshError {
+    fn from(e: rusqlite::Error) -> Self {
+        RefreshError::Database(e)
+    }
+}
+
+pub fn refresh_ttl_secs() -> i64 {
+    std::env::var("ADMIN_REFRESH_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_REFRESH_TTL_SECS)
+}
+
+/// Creates a session row and mints its first access and refresh tokens.
+/// Refresh tokens have the form `<session id>.<secret>`; only a hash of the secret is stored.
+pub fn start(
+    conn: &Connection,
+    token_secret: &[u8],
+    user_id: i64,
+    user_agent: &str,
+    ip: &str,
+) -> Result<SessionTokens> {
+    let session_id = tokens::random(32);
+    let refresh_secret = tokens::random(32);
+    let expires_at = chrono::Utc::now().timestamp() + refresh_ttl_secs();
+
+    conn.execute(
+        "INSERT INTO admin_sessions (id, user_id, refresh_token_hash, user_agent, ip, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![session_id, user_id, tokens::hash(&refresh_secret), user_agent, ip, expires_at],
+    )?;
+
+    Ok(SessionTokens {
+        access_token: auth::issue_token(token_secret, user_id, &session_id),
+        refresh_token: format!("{}.{}", session_id, refresh_secret),
+        session_id,
+    })
+}
+
+/// Trades a refresh token for a new access token, rotating the refresh token.
+/// Presenting an already-rotated refresh token revokes the whole session,
+/// since it means the token was copied.
+pub fn refresh(
+    conn: &Connection,
+    token_secret: &[u8],
+    refresh_token: &str,
+) -> std::result::Result<SessionTokens, RefreshError> {
+    let (session_id, refresh_secret) = refresh_token.split_once('.').ok_or(RefreshError::Invalid)?;
+    let now = chrono::Utc::now().timestamp();
+
+    let session: Option<(i64, String)> = conn.query_row(
+        "SELECT s.user_id, s.refresh_token_hash FROM admin_sessions s
+         JOIN admin_users u ON u.id = s.user_id
+         WHERE s.id = ?1 AND s.revoked_at IS NULL AND s.expires_at > ?2 AND u.disabled = 0",
+        params![session_id, now],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
+
+    let (user_id, stored_hash) = session.ok_or(RefreshError::Invalid)?;
+
+    if stored_hash != tokens::hash(refresh_secret) {
+        tracing::warn!("Refresh token reuse detected, revoking session {}", session_id);
+        revoke(conn, session_id)?;
+        return Err(RefreshError::Invalid);
+    }
+
+    let new_secret = tokens::random(32);
+    conn.execute(
+        "UPDATE admin_sessions SET refresh_token_hash = ?1, last_seen_at = CURRENT_TIMESTAMP WHERE id = ?2",
+        params![tokens::hash(&new_secret), session_id],
+    )?;
+
+    Ok(SessionTokens {
+        access_token: auth::issue_token(token_secret, user_id, session_id),
+        refresh_token: format!("{}.{}", session_id, new_secret),
+        session_id: session_id.to_string(),
+    })
+}
+
+/// Returns whether the session is still live for this user, recording the activity if so.
+pub fn touch(conn: &Connection, session_id: &str, user_id: i64) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE admin_sessions SET last_seen_at = CURRENT_TIMESTAMP
+         WHERE id = ?1 AND user_id = ?2 AND revoked_at IS NULL AND expires_at > ?3",
+        params![session_id, user_id, chrono::Utc::now().timestamp()],
+    )?;
+    Ok(updated > 0)
+}
+
+pub fn revoke(conn: &Connection, session_id: &str) -> Result<usize> {
+    conn.execute(
+        "UPDATE admin_sessions SET revoked_at = CURRENT_TIMESTAMP WHERE id = ?1 AND revoked_at IS NULL",
+        [session_id],
+    )
+}
+
+/// Revokes every session of a user except `keep`, e.g. after a password change.
+pub fn revoke_all_for_user(conn: &Connection, user_id: i64, keep: Option<&str>) -> Result<usize> {
+    conn.execute(
+        "UPDATE admin_sessions SET revoked_at = CURRENT_TIMESTAMP
+         WHERE user_id = ?1 AND revoked_at IS NULL AND id != COALESCE(?2, '')",
+        params![user_id, keep],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::TokenKind;
+    use crate::db::AppState;
+
+    const SECRET: &[u8] = b"test secret";
+
+    /// An in-memory database with one admin, and their id.
+    fn db() -> (Connection, i64) {
+        let conn = AppState::migrate(Connection::open_in_memory().unwrap()).unwrap();
+        conn.execute("INSERT INTO admin_users (username, password_hash) VALUES ('jane', '')", []).unwrap();
+        let user_id = conn.last_insert_rowid();
+        (conn, user_id)
+    }
+
+    fn session_id(tokens: &SessionTokens) -> String {
+        tokens.refresh_token.split_once('.').unwrap().0.to_string()
+    }
+
+    fn refreshes(conn: &Connection, refresh_token: &str) -> bool {
+        refresh(conn, SECRET, refresh_token).is_ok()
+    }
+
+    #[test]
+    fn access_token_names_the_session() {
+        let (conn, user_id) = db();
+        let tokens = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+
+        let claims = auth::verify_token(SECRET, &tokens.access_token, TokenKind::Session).unwrap();
+        assert_eq!(claims.sub, user_id);
+        assert_eq!(claims.sid, Some(session_id(&tokens)));
+    }
+
+    #[test]
+    fn only_a_hash_of_the_refresh_secret_is_stored() {
+        let (conn, user_id) = db();
+        let tokens = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+        let (_, secret) = tokens.refresh_token.split_once('.').unwrap();
+
+        let stored: String = conn.query_row("SELECT refresh_token_hash FROM admin_sessions", [], |row| row.get(0)).unwrap();
+        assert_ne!(stored, secret);
+        assert_eq!(stored, tokens::hash(secret));
+    }
+
+    #[test]
+    fn refresh_rotates_the_refresh_token() {
+        let (conn, user_id) = db();
+        let first = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+
+        let Ok(second) = refresh(&conn, SECRET, &first.refresh_token) else {
+            panic!("a fresh refresh token is accepted");
+        };
+        assert_ne!(second.refresh_token, first.refresh_token);
+        assert_eq!(session_id(&second), session_id(&first));
+        assert!(refreshes(&conn, &second.refresh_token));
+    }
+
+    #[test]
+    fn reusing_a_rotated_refresh_token_revokes_the_session() {
+        let (conn, user_id) = db();
+        let first = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+        let Ok(second) = refresh(&conn, SECRET, &first.refresh_token) else {
+            panic!("a fresh refresh token is accepted");
+        };
+
+        // Whoever copied the first token replays it: both copies stop working
+        assert!(!refreshes(&conn, &first.refresh_token));
+        assert!(!refreshes(&conn, &second.refresh_token));
+        assert!(!touch(&conn, &session_id(&first), user_id).unwrap());
+    }
+
+    #[test]
+    fn forged_refresh_tokens_are_rejected() {
+        let (conn, user_id) = db();
+        let tokens = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+
+        let (_, secret) = tokens.refresh_token.split_once('.').unwrap();
+
+        assert!(!refreshes(&conn, "no separator"));
+        assert!(!refreshes(&conn, &format!("unknown-session.{}", secret)));
+    }
+
+    #[test]
+    fn expired_or_disabled_sessions_cannot_refresh() {
+        let (conn, user_id) = db();
+
+        let expired = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+        conn.execute(
+            "UPDATE admin_sessions SET expires_at = ?1 WHERE id = ?2",
+            params![chrono::Utc::now().timestamp(), session_id(&expired)],
+        ).unwrap();
+        assert!(!refreshes(&conn, &expired.refresh_token));
+
+        let live = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+        conn.execute("UPDATE admin_users SET disabled = 1 WHERE id = ?1", [user_id]).unwrap();
+        assert!(!refreshes(&conn, &live.refresh_token));
+    }
+
+    #[test]
+    fn touch_only_accepts_live_sessions_of_their_owner() {
+        let (conn, user_id) = db();
+        let tokens = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+        let id = session_id(&tokens);
+
+        assert!(touch(&conn, &id, user_id).unwrap());
+        assert!(!touch(&conn, &id, user_id + 1).unwrap());
+
+        revoke(&conn, &id).unwrap();
+        assert!(!touch(&conn, &id, user_id).unwrap());
+        assert!(!refreshes(&conn, &tokens.refresh_token));
+    }
+
+    #[test]
+    fn revoke_all_keeps_only_the_named_session() {
+        let (conn, user_id) = db();
+        let current = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+        let other = start(&conn, SECRET, user_id, "test", "127.0.0.1").unwrap();
+
+        assert_eq!(revoke_all_for_user(&conn, user_id, Some(&session_id(&current))).unwrap(), 1);
+        assert!(touch(&conn, &session_id(&current), user_id).unwrap());
+        assert!(!touch(&conn, &session_id(&other), user_id).unwrap());
+
+        assert_eq!(revoke_all_for_user(&conn, user_id, None).unwrap(), 1);
+        assert!(!touch(&conn, &session_id(&current), user_id).unwrap());
+    }
+}
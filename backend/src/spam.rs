@@ -0,0 +1,181 @@
+// Heuristic spam scoring for contact submissions. Each signal adds points;
+// anything at or above the threshold is saved as spam and never emailed.
+
+// Score at which a message counts as spam unless SPAM_SCORE_THRESHOLD says otherwise
+const DEFAULT_THRESHOLD: f64 = 5.0;
+
+// Phrases that show up in SEO, crypto and other bulk spam, matched case-insensitively
+const SPAM_PHRASES: &[&str] = &[
+    "seo services",
+    "search engine optimization",
+    "backlinks",
+    "guest post",
+    "rank your website",
+    "first page of google",
+    "increase your traffic",
+    "web traffic",
+    "website traffic",
+    "lead generation",
+    "buy now",
+    "limited time offer",
+    "act now",
+    "click here",
+    "100% free",
+    "risk-free",
+    "earn money",
+    "make money online",
+    "work from home",
+    "double your",
+    "crypto",
+    "bitcoin",
+    "forex",
+    "investment opportunity",
+    "casino",
+    "viagra",
+    "cialis",
+    "loan offer",
+    "dear friend",
+    "congratulations you",
+    "you have been selected",
+    "unsubscribe",
+];
+
+pub fn threshold() -> f64 {
+    std::env::var("SPAM_SCORE_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_THRESHOLD)
+}
+
+pub fn is_spam(score: f64) -> bool {
+    score >= threshold()
+}
+
+fn count_links(text: &str) -> usize {
+    text.matches("http://").count() + text.matches("https://").count() + text.matches("www.").count()
+}
+
+/// Latin script covers ASCII letters plus the Latin-1 and Latin Extended blocks.
+fn is_latin(c: char) -> bool {
+    c.is_ascii_alphabetic() || ('\u{00C0}'..='\u{024F}').contains(&c)
+}
+
+/// Words of five or more letters with no vowels or a run of five consonants,
+/// the shape of keyboard-mashed filler like "xkcdqwrt".
+fn looks_like_gibberish(word: &str) -> bool {
+    let letters: Vec<char> = word.chars().filter(|c| c.is_ascii_alphabetic()).map(|c| c.to_ascii_lowercase()).collect();
+    if letters.len() < 5 {
+        return false;
+    }
+
+    let is_vowel = |c: &char| "aeiouy".contains(*c);
+    if !letters.iter().any(is_vowel) {
+        return true;
+    }
+
+    let mut run = 0;
+    for c in &letters {
+        run = if is_vowel(c) { 0 } else { run + 1 };
+        if run >= 5 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Scores a submission; higher is spammier.
+pub fn score(name: &str, email: &str, subject: &str, message: &str) -> f64 {
+    let text = format!("{}\n{}\n{}", name, subject, message);
+    let lower = text.to_lowercase();
+    let mut score = 0.0;
+
+    // One link is normal ("here's my site"), a handful is not
+    let links = count_links(&lower);
+    if links > 0 {
+        score += 0.5 + (links - 1) as f64;
+    }
+
+    score += 1.5 * SPAM_PHRASES.iter().filter(|phrase| lower.contains(*phrase)).count() as f64;
+
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    if !letters.is_empty() {
+        let non_latin = letters.iter().filter(|c| !is_latin(**c)).count() as f64 / letters.len() as f64;
+        if non_latin > 0.3 {
+            score += 3.0 * non_latin;
+        }
+
+        let upper = letters.iter().filter(|c| c.is_uppercase()).count() as f64 / letters.len() as f64;
+        if letters.len() >= 20 && upper > 0.6 {
+            score += 1.5;
+        }
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if !words.is_empty() {
+        let gibberish = words.iter().filter(|w| looks_like_gibberish(w)).count() as f64 / words.len() as f64;
+        if gibberish > 0.2 {
+            score += 4.0 * gibberish;
+        }
+    }
+
+    // Bots often paste a link or the address itself into the name field
+    if count_links(&name.to_lowercase()) > 0 || name.contains('@') {
+        score += 2.0;
+    }
+    if email.split('@').next().is_some_and(|local| local.chars().filter(|c| c.is_ascii_digit()).count() >= 6) {
+        score += 0.5;
+    }
+
+    (score * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordinary_enquiries_score_low() {
+        let score = score(
+            "Jane Doe",
+            "jane@example.com",
+            "Freelance project",
+            "Hi, I saw your portfolio and would like to talk about a small web app. My site is https://example.com.",
+        );
+        assert!(score < DEFAULT_THRESHOLD, "{}", score);
+    }
+
+    #[test]
+    fn link_stuffed_seo_pitches_score_high() {
+        let score = score(
+            "SEO Expert",
+            "seo1234567@example.com",
+            "Rank your website on the first page of Google",
+            "Our SEO services bring backlinks and website traffic. Click here: http://a.example http://b.example www.c.example",
+        );
+        assert!(score >= DEFAULT_THRESHOLD, "{}", score);
+    }
+
+    #[test]
+    fn links_or_addresses_in_the_name_count_against_it() {
+        let plain = score("Jane", "jane@example.com", "Hello", "Hello there");
+        assert_eq!(score("jane@example.com", "jane@example.com", "Hello", "Hello there") - plain, 2.0);
+        assert_eq!(score("www.example.com", "jane@example.com", "Hello", "Hello there") - plain, 2.5);
+    }
+
+    #[test]
+    fn keyboard_mash_is_gibberish() {
+        assert!(looks_like_gibberish("xkcdqwrt"));
+        assert!(looks_like_gibberish("bcdfg"));
+        assert!(!looks_like_gibberish("strength"));
+        assert!(!looks_like_gibberish("hello"));
+        assert!(!looks_like_gibberish("zzz"));
+    }
+
+    #[test]
+    fn accented_latin_letters_are_latin() {
+        assert!(is_latin('é'));
+        assert!(is_latin('ß'));
+        assert!(!is_latin('Ж'));
+        assert!(!is_latin('漢'));
+    }
+}
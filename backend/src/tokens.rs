@@ -0,0 +1,19 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+// Bearer secrets handed out once (API keys, refresh tokens, reset links, webhook secrets)
+// and looked up later by hash. They're random enough that a plain SHA-256 is sufficient
+// at rest; bcrypt is for passwords people choose.
+
+/// `len` random bytes in base64url, without padding.
+pub fn random(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The hex SHA-256 stored in place of a token.
+pub fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
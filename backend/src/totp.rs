@@ -0,0 +1,71 @@
+use rand::Rng;
+use totp_rs::{Algorithm, Secret, TOTP};
+use crate::tokens;
+
+const DIGITS: usize = 6;
+const STEP_SECS: u64 = 30;
+// Accept codes from one step either side of now to allow for clock drift
+const SKEW: u64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+fn issuer() -> String {
+    std::env::var("TOTP_ISSUER")
+        .unwrap_or_else(|_| "Portfolio Admin".to_string())
+        .replace(':', "")
+}
+
+fn build(secret: &str, username: &str) -> Option<TOTP> {
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    TOTP::new(Algorithm::SHA1, DIGITS, SKEW as u8, STEP_SECS, bytes, Some(issuer()), username.replace(':', "")).ok()
+}
+
+/// Generates a new base32-encoded TOTP secret.
+pub fn new_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+/// The `otpauth://` URI authenticator apps scan to enroll the secret.
+pub fn provisioning_uri(secret: &str, username: &str) -> Option<String> {
+    build(secret, username).map(|totp| totp.get_url())
+}
+
+/// Returns the time step the code was generated for, if it is valid right now.
+/// Callers store the step so the same code can't be replayed.
+pub fn matching_step(secret: &str, code: &str) -> Option<u64> {
+    let totp = build(secret, "")?;
+    let now = chrono::Utc::now().timestamp() as u64;
+    let current = now / STEP_SECS;
+
+    (current.saturating_sub(SKEW)..=current + SKEW)
+        .find(|step| constant_time_eq(totp.generate(step * STEP_SECS).as_bytes(), code.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Generates single-use recovery codes in the form `xxxxx-xxxxx`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let mut rng = rand::thread_rng();
+
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Recovery codes are random enough to be stored as plain token hashes.
+/// Dashes, spaces and case are ignored so codes can be typed loosely.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    tokens::hash(&normalized)
+}
@@ -0,0 +1,410 @@
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::db::AppState;
+use crate::queue::{self, Queue, Retry, RetryPolicy};
+use crate::tokens;
+
+// Outgoing webhooks. When something happens, one delivery per subscribed webhook is
+// written alongside the change, and a background worker POSTs them, retrying failures
+// with exponential backoff (see queue.rs). Every request is signed:
+//
+//   X-Webhook-Timestamp: <unix seconds>
+//   X-Webhook-Signature: sha256=<hex HMAC-SHA256 of "<timestamp>.<body>" with the webhook's secret>
+//
+// Receivers should recompute the signature and reject old timestamps. A webhook's
+// format is 'json' (the event envelope), or 'slack' / 'discord', which post a one-line
+// summary to an incoming webhook URL of that service instead.
+
+/// Events a webhook can subscribe to. `ping` is only sent by the test endpoint.
+pub const EVENTS: &[&str] = &["contact.created", "project.created", "project.updated", "project.deleted"];
+pub const PING_EVENT: &str = "ping";
+pub const FORMATS: &[&str] = &["json", "slack", "discord"];
+
+const SECRET_PREFIX: &str = "whsec_";
+
+const RETRY: RetryPolicy = RetryPolicy::new("WEBHOOK_MAX_ATTEMPTS", "WEBHOOK_BACKOFF_BASE_SECS");
+// A receiver that takes longer than this counts as failed
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Discord rejects messages longer than this
+const MAX_DISCORD_CONTENT_LEN: usize = 2000;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A fresh signing secret, `whsec_` followed by 32 random bytes in base64url.
+pub fn new_secret() -> String {
+    format!("{}{}", SECRET_PREFIX, tokens::random(32))
+}
+
+/// The `X-Webhook-Signature` value for `body` sent at `timestamp`.
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Whether `ip` is on the public internet. Anything else (the server itself, the private
+/// network it sits on, a cloud metadata service at 169.254.169.254) is off limits to webhooks.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+// For trying webhooks out against a receiver on the same machine or network
+fn private_targets_allowed() -> bool {
+    std::env::var("WEBHOOK_ALLOW_PRIVATE_TARGETS").is_ok_and(|v| v == "true" || v == "1")
+}
+
+/// Resolves the host of `url`, returning it with the address to connect to. Fails if
+/// the host doesn't resolve, or if any address it resolves to isn't public.
+pub async fn resolve_target(url: &str) -> std::result::Result<(String, SocketAddr), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+    // IPv6 literals come back in brackets
+    let host = parsed.host_str().ok_or("URL has no host")?.trim_start_matches('[').trim_end_matches(']');
+    let port = parsed.port_or_known_default().ok_or("URL has no port")?;
+
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Couldn't resolve {}: {}", host, e))?
+        .collect();
+
+    if !private_targets_allowed() {
+        if let Some(address) = addresses.iter().find(|address| !is_public(address.ip())) {
+            return Err(format!("{} resolves to non-public address {}", host, address.ip()));
+        }
+    }
+
+    addresses
+        .first()
+        .map(|address| (host.to_string(), *address))
+        .ok_or_else(|| format!("{} has no addresses", host))
+}
+
+pub fn events_to_string(events: &[String]) -> String {
+    events.join(" ")
+}
+
+pub fn events_from_string(events: &str) -> Vec<String> {
+    events.split_whitespace().map(str::to_string).collect()
+}
+
+/// The request body for one event, in a webhook's format.
+fn payload(format: &str, event: &str, data: &Value) -> String {
+    match format {
+        "slack" => json!({ "text": slack_escape(&summary(event, data)) }).to_string(),
+        "discord" => json!({
+            "content": summary(event, data).chars().take(MAX_DISCORD_CONTENT_LEN).collect::<String>(),
+            // Text from the contact form must not be able to ping anyone
+            "allowed_mentions": { "parse": [] },
+        }).to_string(),
+        _ => json!({
+            "event": event,
+            "created_at": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "data": data,
+        }).to_string(),
+    }
+}
+
+/// One line describing an event, for chat services.
+fn summary(event: &str, data: &Value) -> String {
+    let field = |object: &str, name: &str| data[object][name].as_str().unwrap_or_default().to_string();
+
+    match event {
+        "contact.created" => format!(
+            "New message from {} <{}>: {}",
+            field("contact", "name"), field("contact", "email"), field("contact", "subject"),
+        ),
+        "project.created" => format!("Project created: {}", field("project", "title")),
+        "project.updated" => format!("Project updated: {}", field("project", "title")),
+        "project.deleted" => format!("Project deleted: {}", field("project", "title")),
+        PING_EVENT => "Test delivery: this webhook is set up correctly.".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Slack reads `&`, `<` and `>` as markup in message text.
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Queues `event` for every active webhook subscribed to it. Returns how many deliveries were queued.
+pub fn enqueue(conn: &Connection, event: &str, data: &Value) -> Result<usize> {
+    let mut stmt = conn.prepare("SELECT id, events, format FROM webhooks WHERE active = 1")?;
+    let webhooks = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+    })?.collect::<Result<Vec<_>>>()?;
+
+    let mut queued = 0;
+    for (webhook_id, events, format) in webhooks {
+        if events_from_string(&events).iter().any(|subscribed| subscribed == event) {
+            enqueue_for(conn, webhook_id, &format, event, data)?;
+            queued += 1;
+        }
+    }
+
+    Ok(queued)
+}
+
+/// Queues `event` for one webhook, whatever it subscribes to. Returns the delivery id.
+pub fn enqueue_for(conn: &Connection, webhook_id: i64, format: &str, event: &str, data: &Value) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload, next_attempt_at) VALUES (?1, ?2, ?3, ?4)",
+        params![webhook_id, event, payload(format, event, data), chrono::Utc::now().timestamp()],
+    )?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+struct Due {
+    id: i64,
+    event: String,
+    payload: String,
+    url: String,
+    secret: String,
+}
+
+/// What came of one POST. `error` is set for anything but a 2xx response.
+struct Attempt {
+    response_status: Option<u16>,
+    error: Option<String>,
+}
+
+impl Attempt {
+    fn failed(error: String) -> Self {
+        Attempt { response_status: None, error: Some(error) }
+    }
+}
+
+/// The pending delivery that has been due the longest. Deliveries for a webhook that
+/// has since been deactivated wait until it's turned back on.
+fn next_due(conn: &Connection) -> Result<Option<Due>> {
+    conn.query_row(
+        "SELECT d.id, d.event, d.payload, w.url, w.secret
+         FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+         WHERE d.status = 'pending' AND d.next_attempt_at <= ?1 AND w.active = 1
+         ORDER BY d.next_attempt_at, d.id LIMIT 1",
+        [chrono::Utc::now().timestamp()],
+        |row| Ok(Due {
+            id: row.get(0)?,
+            event: row.get(1)?,
+            payload: row.get(2)?,
+            url: row.get(3)?,
+            secret: row.get(4)?,
+        }),
+    ).optional()
+}
+
+async fn deliver(due: &Due) -> Attempt {
+    // Checked again on every attempt, since DNS may have changed since the webhook was saved
+    let (host, address) = match resolve_target(&due.url).await {
+        Ok(target) => target,
+        Err(e) => return Attempt::failed(e),
+    };
+
+    let timestamp = chrono::Utc::now().timestamp();
+
+    let client = match reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        // A redirect would send the signed payload somewhere nobody configured
+        .redirect(reqwest::redirect::Policy::none())
+        // Connect to the address just checked, not whatever a second lookup returns
+        .resolve(&host, address)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => return Attempt::failed(e.to_string()),
+    };
+
+    let started = Instant::now();
+    let response = client
+        .post(&due.url)
+        .header("Content-Type", "application/json")
+        .header("User-Agent", "josh-portfolio-webhooks")
+        .header("X-Webhook-Event", &due.event)
+        .header("X-Webhook-Delivery", due.id.to_string())
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", signature(&due.secret, timestamp, &due.payload))
+        .body(due.payload.clone())
+        .send()
+        .await;
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => return Attempt::failed(e.to_string()),
+    };
+
+    // The response body isn't kept: it would let anyone who can edit a webhook read
+    // whatever the server can reach
+    let status = response.status();
+    tracing::info!("Webhook delivery {} to {}: {} in {:?}", due.id, due.url, status, started.elapsed());
+
+    Attempt {
+        response_status: Some(status.as_u16()),
+        error: (!status.is_success()).then(|| format!("HTTP {}", status)),
+    }
+}
+
+/// Marks a delivery delivered, or schedules its next attempt, or parks it as failed.
+fn record_attempt(conn: &Connection, id: i64, attempt: &Attempt) -> Result<()> {
+    let Some(error) = &attempt.error else {
+        conn.execute(
+            "UPDATE webhook_deliveries SET status = 'delivered', attempts = attempts + 1, last_error = NULL,
+             response_status = ?1, delivered_at = CURRENT_TIMESTAMP WHERE id = ?2",
+            params![attempt.response_status, id],
+        )?;
+        return Ok(());
+    };
+
+    let attempts: i64 = conn.query_row(
+        "SELECT attempts + 1 FROM webhook_deliveries WHERE id = ?1",
+        [id],
+        |row| row.get(0),
+    )?;
+
+    match RETRY.after_failure(attempts) {
+        Retry::GiveUp => {
+            tracing::error!("Giving up on webhook delivery {} after {} attempts: {}", id, attempts, error);
+            conn.execute(
+                "UPDATE webhook_deliveries SET status = 'failed', attempts = ?1, last_error = ?2,
+                 response_status = ?3 WHERE id = ?4",
+                params![attempts, error, attempt.response_status, id],
+            )?;
+        }
+        Retry::At(next_attempt_at) => {
+            tracing::warn!("Webhook delivery {} failed (attempt {}), retrying at {}: {}", id, attempts, next_attempt_at, error);
+            conn.execute(
+                "UPDATE webhook_deliveries SET attempts = ?1, last_error = ?2, response_status = ?3,
+                 next_attempt_at = ?4 WHERE id = ?5",
+                params![attempts, error, attempt.response_status, next_attempt_at, id],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Puts an undelivered delivery back in the queue with a fresh set of attempts.
+/// Returns false if there's no such delivery or it was already delivered.
+pub fn retry(conn: &Connection, id: i64) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE webhook_deliveries SET status = 'pending', attempts = 0, next_attempt_at = ?1
+         WHERE id = ?2 AND status != 'delivered'",
+        params![chrono::Utc::now().timestamp(), id],
+    )?;
+    Ok(updated > 0)
+}
+
+struct Webhooks;
+
+#[async_trait]
+impl Queue for Webhooks {
+    type Item = Due;
+    type Outcome = Attempt;
+
+    const NAME: &'static str = "Webhook";
+
+    fn next_due(&self, conn: &Connection) -> Result<Option<Due>> {
+        next_due(conn)
+    }
+
+    async fn deliver(&self, due: &Due) -> Attempt {
+        deliver(due).await
+    }
+
+    fn record(&self, conn: &Connection, due: &Due, attempt: &Attempt) -> Result<()> {
+        record_attempt(conn, due.id, attempt)
+    }
+}
+
+/// Delivers queued webhooks forever.
+pub async fn run(state: Arc<AppState>) {
+    queue::run(state, Webhooks).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_hmac_of_timestamp_and_body() {
+        // What a receiver gets from hmac.new(b"whsec_test", b"1700000000.{}", sha256) in Python
+        assert_eq!(
+            signature("whsec_test", 1700000000, "{}"),
+            "sha256=35495024f4ef3f94e5a93e22221544c4b75e9a42300cd965ab81cb85cd994e91",
+        );
+    }
+
+    #[test]
+    fn signature_covers_secret_timestamp_and_body() {
+        let original = signature("whsec_test", 1700000000, r#"{"event":"ping"}"#);
+
+        assert_ne!(signature("whsec_other", 1700000000, r#"{"event":"ping"}"#), original);
+        // A captured request can't be replayed later under a fresh timestamp
+        assert_ne!(signature("whsec_test", 1700000300, r#"{"event":"ping"}"#), original);
+        assert_ne!(signature("whsec_test", 1700000000, r#"{"event":"pong"}"#), original);
+        // Moving the separator between timestamp and body changes the signature too
+        assert_ne!(signature("whsec_test", 170000000, r#"0.{"event":"ping"}"#), original);
+    }
+
+    #[test]
+    fn secrets_are_unique_and_prefixed() {
+        let (a, b) = (new_secret(), new_secret());
+        assert!(a.starts_with(SECRET_PREFIX));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1", "10.0.0.1", "172.16.5.4", "192.168.1.1", "169.254.169.254", "0.0.0.0", "0.1.2.3",
+            "100.64.0.1", "100.127.255.254", "255.255.255.255", "224.0.0.1", "192.0.2.1",
+            "::1", "::", "fc00::1", "fd12:3456::1", "fe80::1", "ff02::1", "::ffff:127.0.0.1", "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn internet_addresses_are_public() {
+        for ip in ["93.184.215.14", "1.1.1.1", "100.63.255.255", "100.128.0.1", "172.32.0.1", "2606:4700::1111", "::ffff:1.1.1.1"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn slack_summaries_cannot_inject_markup() {
+        let data = json!({ "contact": { "name": "<!channel>", "email": "a@b.co", "subject": "x & y" } });
+        let body: Value = serde_json::from_str(&payload("slack", "contact.created", &data)).unwrap();
+        assert_eq!(body["text"], "New message from &lt;!channel&gt; &lt;a@b.co&gt;: x &amp; y");
+    }
+}